@@ -5,7 +5,7 @@ use ray_tracer::geometry::*;
 use ray_tracer::raytracer::*;
 use ray_tracer::shape::*;
 
-fn create_world<'a>(lights: Vec<PointLight>) -> World<'a> {
+fn create_world(lights: Vec<PointLight>) -> World {
     let material = Material::new()
         .with_color(Color::new(1.0, 0.9, 0.9))
         .with_specular(0.0);
@@ -65,12 +65,10 @@ fn create_world<'a>(lights: Vec<PointLight>) -> World<'a> {
                 .with_specular(0.3),
         );
 
-    let world = World::new()
+    World::new()
         .with_lights(lights)
         .with_objects(vec![floor, right_wall, left_wall])
-        .with_objects(vec![middle, right, left]);
-
-    world
+        .with_objects(vec![middle, right, left])
 }
 
 fn main() {
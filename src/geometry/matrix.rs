@@ -49,7 +49,7 @@ impl Matrix {
         result
     }
 
-    fn determinant(&self) -> f64 {
+    pub(crate) fn determinant(&self) -> f64 {
         if self.size == 2 {
             self.data[0][0] * self.data[1][1] - self.data[0][1] * self.data[1][0]
         } else {
@@ -84,6 +84,7 @@ impl Matrix {
         self.submatrix(row, col).determinant()
     }
 
+    #[allow(clippy::manual_is_multiple_of)]
     fn cofactor(&self, row: usize, col: usize) -> f64 {
         if (row + col) % 2 == 0 {
             self.minor(row, col)
@@ -92,7 +93,7 @@ impl Matrix {
         }
     }
 
-    fn is_invertible(&self) -> bool {
+    pub(crate) fn is_invertible(&self) -> bool {
         !feq(self.determinant(), 0.0)
     }
 
@@ -0,0 +1,217 @@
+use crate::geometry::{Matrix, Quaternion, Tuple, Vector};
+
+/// A transformation that changes over time, interpolated between a start
+/// (at time 0) and an end (at time 1). The keys are decomposed into
+/// translation, rotation and scale, so that rotating objects sweep through
+/// an arc instead of shrinking through a straight-line blend of matrices.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Motion {
+    start: Decomposed,
+    end: Decomposed,
+}
+
+impl Motion {
+    /// Both keys must be composed of translation, rotation and scaling only;
+    /// shearing is not preserved.
+    pub fn new(start: Matrix, end: Matrix) -> Motion {
+        Motion {
+            start: Decomposed::from(start),
+            end: Decomposed::from(end),
+        }
+    }
+
+    /// Returns the transformation at time `t`, which is clamped to `[0, 1]`.
+    pub fn at(&self, t: f64) -> Matrix {
+        let t = t.clamp(0.0, 1.0);
+        let lerp = |a: Vector, b: Vector| a * (1.0 - t) + b * t;
+
+        let translation = lerp(self.start.translation, self.end.translation);
+        let rotation = self.start.rotation.slerp(self.end.rotation, t);
+        let scale = lerp(self.start.scale, self.end.scale);
+
+        Decomposed {
+            translation,
+            rotation,
+            scale,
+        }
+        .compose()
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Decomposed {
+    translation: Vector,
+    rotation: Quaternion,
+    scale: Vector,
+}
+
+impl Decomposed {
+    // Splits m into T * R * S. The columns of the upper 3x3 part of R * S are
+    // the rotated axes, each scaled by its scale factor.
+    fn from(m: Matrix) -> Decomposed {
+        let translation = Vector::new(m[(0, 3)], m[(1, 3)], m[(2, 3)]);
+
+        let column = |c: usize| Vector::new(m[(0, c)], m[(1, c)], m[(2, c)]);
+        let (x, y, z) = (column(0), column(1), column(2));
+        let mut scale = [x.mag(), y.mag(), z.mag()];
+
+        // a reflection can't be represented as a rotation, so fold it into
+        // the scale instead
+        if x.dot(y.cross(z)) < 0.0 {
+            scale[0] = -scale[0];
+        }
+
+        let [x, y, z] = unit_axes([x, y, z], scale);
+        let rotation = Matrix::with_data([
+            [x.x(), y.x(), z.x(), 0.0],
+            [x.y(), y.y(), z.y(), 0.0],
+            [x.z(), y.z(), z.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        Decomposed {
+            translation,
+            rotation: Quaternion::from_rotation(&rotation),
+            scale: Vector::new(scale[0], scale[1], scale[2]),
+        }
+    }
+
+    fn compose(&self) -> Matrix {
+        let (t, s) = (self.translation, self.scale);
+        (self.rotation.to_matrix() * Matrix::new().scale(s.x(), s.y(), s.z())).translate(
+            t.x(),
+            t.y(),
+            t.z(),
+        )
+    }
+}
+
+// Divides each axis by its scale. An axis scaled to zero has no direction
+// left, so it's rebuilt from the others to complete a right-handed basis;
+// any rotation about the remaining axes is lost, but has no effect anyway.
+fn unit_axes(axes: [Vector; 3], scale: [f64; 3]) -> [Vector; 3] {
+    let mut unit = [
+        Vector::new(1, 0, 0),
+        Vector::new(0, 1, 0),
+        Vector::new(0, 0, 1),
+    ];
+    let mut kept = vec![];
+    for i in 0..3 {
+        if scale[i] != 0.0 {
+            unit[i] = axes[i] / scale[i];
+            kept.push(i);
+        }
+    }
+
+    // each axis is the cross product of the next two, cyclically
+    match kept[..] {
+        [i, j] => {
+            let k = 3 - i - j;
+            unit[k] = unit[(k + 1) % 3].cross(unit[(k + 2) % 3]);
+        }
+        [i] => {
+            let a = unit[i];
+            let helper = if a.x().abs() < 0.9 {
+                Vector::new(1, 0, 0)
+            } else {
+                Vector::new(0, 1, 0)
+            };
+            let b = a.cross(helper).norm();
+            unit[(i + 1) % 3] = b;
+            unit[(i + 2) % 3] = a.cross(b);
+        }
+        _ => {}
+    }
+    unit
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Point;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn decomposing_and_recomposing_a_transformation() {
+        let m = Matrix::new()
+            .scale(2, 3, 0.5)
+            .rotate_x(0.4)
+            .rotate_z(-1.1)
+            .translate(5, -2, 1);
+
+        assert_eq!(Decomposed::from(m).compose(), m);
+    }
+
+    #[test]
+    fn decomposing_a_reflection() {
+        let m = Matrix::new()
+            .scale(-1, 2, 1)
+            .rotate_y(0.7)
+            .translate(1, 2, 3);
+
+        assert_eq!(Decomposed::from(m).compose(), m);
+    }
+
+    #[test]
+    fn decomposing_a_transformation_with_a_zero_scale() {
+        let flat = Matrix::new()
+            .scale(0, 2, 1)
+            .rotate_y(0.7)
+            .translate(1, 2, 3);
+        let line = Matrix::new().scale(0, 3, 0).rotate_x(0.4).rotate_z(1.2);
+        let point = Matrix::new().scale_u(0).translate(1, 2, 3);
+
+        assert_eq!(Decomposed::from(flat).compose(), flat);
+        assert_eq!(Decomposed::from(line).compose(), line);
+        assert_eq!(Decomposed::from(point).compose(), point);
+    }
+
+    #[test]
+    fn a_motion_can_grow_from_nothing() {
+        let motion = Motion::new(Matrix::new().scale_u(0), Matrix::new().rotate_y(1.0));
+
+        assert_eq!(motion.at(1.0), Matrix::new().rotate_y(1.0));
+        assert_eq!(motion.at(0.5), Matrix::new().scale_u(0.5).rotate_y(0.5));
+    }
+
+    #[test]
+    fn a_motion_starts_and_ends_at_its_keys() {
+        let start = Matrix::new().translate(1, 0, 0);
+        let end = Matrix::new().scale_u(2).rotate_y(1.0).translate(0, 3, 0);
+        let motion = Motion::new(start, end);
+
+        assert_eq!(motion.at(0.0), start);
+        assert_eq!(motion.at(1.0), end);
+        assert_eq!(motion.at(-1.0), start);
+        assert_eq!(motion.at(2.0), end);
+    }
+
+    #[test]
+    fn interpolating_a_translation() {
+        let motion = Motion::new(Matrix::new(), Matrix::new().translate(2, 4, -6));
+
+        assert_eq!(motion.at(0.5), Matrix::new().translate(1, 2, -3));
+    }
+
+    #[test]
+    fn interpolating_a_rotation_sweeps_through_an_arc() {
+        let motion = Motion::new(Matrix::new(), Matrix::new().rotate_z(PI / 2.0));
+
+        let m = motion.at(0.5);
+
+        assert_eq!(m, Matrix::new().rotate_z(PI / 4.0));
+        // a blend of the two matrices would pull the point toward the origin
+        assert!((((m * Point::new(1, 0, 0)) - Point::new(0, 0, 0)).mag() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn interpolating_a_scale() {
+        let motion = Motion::new(Matrix::new().scale(1, 2, 3), Matrix::new().scale(3, 2, 1));
+
+        assert_eq!(motion.at(0.25), Matrix::new().scale(1.5, 2, 2.5));
+    }
+}
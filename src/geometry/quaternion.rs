@@ -0,0 +1,218 @@
+use crate::feq;
+use crate::geometry::Matrix;
+
+/// A unit quaternion representing a rotation, used to interpolate smoothly
+/// between orientations.
+#[derive(Debug, Copy, Clone)]
+pub struct Quaternion {
+    w: f64,
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+impl Quaternion {
+    pub fn new<W: Into<f64>, X: Into<f64>, Y: Into<f64>, Z: Into<f64>>(
+        w: W,
+        x: X,
+        y: Y,
+        z: Z,
+    ) -> Quaternion {
+        Quaternion {
+            w: w.into(),
+            x: x.into(),
+            y: y.into(),
+            z: z.into(),
+        }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1, 0, 0, 0)
+    }
+
+    /// Extracts the rotation from the upper 3x3 part of a matrix, which must
+    /// be a pure rotation (orthonormal, with a determinant of 1).
+    pub fn from_rotation(m: &Matrix) -> Quaternion {
+        let trace = m[(0, 0)] + m[(1, 1)] + m[(2, 2)];
+
+        // pick the largest of w, x, y and z to divide by, for stability
+        let q = if trace > 0.0 {
+            let s = 2.0 * (trace + 1.0).sqrt();
+            Quaternion::new(
+                s / 4.0,
+                (m[(2, 1)] - m[(1, 2)]) / s,
+                (m[(0, 2)] - m[(2, 0)]) / s,
+                (m[(1, 0)] - m[(0, 1)]) / s,
+            )
+        } else if m[(0, 0)] > m[(1, 1)] && m[(0, 0)] > m[(2, 2)] {
+            let s = 2.0 * (1.0 + m[(0, 0)] - m[(1, 1)] - m[(2, 2)]).sqrt();
+            Quaternion::new(
+                (m[(2, 1)] - m[(1, 2)]) / s,
+                s / 4.0,
+                (m[(0, 1)] + m[(1, 0)]) / s,
+                (m[(0, 2)] + m[(2, 0)]) / s,
+            )
+        } else if m[(1, 1)] > m[(2, 2)] {
+            let s = 2.0 * (1.0 + m[(1, 1)] - m[(0, 0)] - m[(2, 2)]).sqrt();
+            Quaternion::new(
+                (m[(0, 2)] - m[(2, 0)]) / s,
+                (m[(0, 1)] + m[(1, 0)]) / s,
+                s / 4.0,
+                (m[(1, 2)] + m[(2, 1)]) / s,
+            )
+        } else {
+            let s = 2.0 * (1.0 + m[(2, 2)] - m[(0, 0)] - m[(1, 1)]).sqrt();
+            Quaternion::new(
+                (m[(1, 0)] - m[(0, 1)]) / s,
+                (m[(0, 2)] + m[(2, 0)]) / s,
+                (m[(1, 2)] + m[(2, 1)]) / s,
+                s / 4.0,
+            )
+        };
+        q.norm()
+    }
+
+    pub fn to_matrix(&self) -> Matrix {
+        let Quaternion { w, x, y, z } = *self;
+        Matrix::with_data([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn dot(&self, other: Self) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn norm(&self) -> Self {
+        let mag = self.dot(*self).sqrt();
+        Quaternion::new(self.w / mag, self.x / mag, self.y / mag, self.z / mag)
+    }
+
+    /// Spherical linear interpolation, taking the shortest path between the
+    /// two rotations.
+    pub fn slerp(&self, other: Self, t: f64) -> Self {
+        // q and -q represent the same rotation; pick the nearer one
+        let mut cos_theta = self.dot(other);
+        let other = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            Quaternion::new(-other.w, -other.x, -other.y, -other.z)
+        } else {
+            other
+        };
+
+        let (a, b) = if cos_theta > 0.9995 {
+            // nearly parallel, so fall back to a linear blend
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+
+        Quaternion::new(
+            a * self.w + b * other.w,
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+        )
+        .norm()
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl PartialEq for Quaternion {
+    // q and -q represent the same rotation
+    fn eq(&self, other: &Self) -> bool {
+        feq(self.dot(*other).abs(), 1.0)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+    #[test]
+    fn the_identity_quaternion_is_the_identity_rotation() {
+        assert_eq!(Quaternion::identity().to_matrix(), Matrix::new());
+        assert_eq!(
+            Quaternion::from_rotation(&Matrix::new()),
+            Quaternion::identity()
+        );
+    }
+
+    #[test]
+    fn a_quaternion_from_a_rotation_about_y() {
+        let q = Quaternion::from_rotation(&Matrix::new().rotate_y(PI / 2.0));
+
+        assert_eq!(q, Quaternion::new(FRAC_1_SQRT_2, 0, FRAC_1_SQRT_2, 0));
+    }
+
+    #[test]
+    fn rotations_survive_a_round_trip_through_a_quaternion() {
+        let rotations = [
+            Matrix::new().rotate_x(0.3).rotate_y(-1.2).rotate_z(2.5),
+            Matrix::new().rotate_x(PI),
+            Matrix::new().rotate_y(PI),
+            Matrix::new().rotate_z(PI),
+            Matrix::new().rotate_x(PI).rotate_y(PI / 2.0),
+        ];
+
+        for m in rotations {
+            assert_eq!(Quaternion::from_rotation(&m).to_matrix(), m);
+        }
+    }
+
+    #[test]
+    fn slerp_interpolates_the_rotation_angle() {
+        let a = Quaternion::identity();
+        let b = Quaternion::from_rotation(&Matrix::new().rotate_z(PI / 2.0));
+
+        assert_eq!(a.slerp(b, 0.0), a);
+        assert_eq!(a.slerp(b, 1.0), b);
+        assert_eq!(
+            a.slerp(b, 0.5).to_matrix(),
+            Matrix::new().rotate_z(PI / 4.0)
+        );
+    }
+
+    #[test]
+    fn slerp_takes_the_shortest_path() {
+        let a = Quaternion::identity();
+        let b = Quaternion::from_rotation(&Matrix::new().rotate_x(PI / 2.0));
+        let negated_b = Quaternion::new(-b.w, -b.x, -b.y, -b.z);
+
+        assert_eq!(
+            a.slerp(negated_b, 0.5).to_matrix(),
+            Matrix::new().rotate_x(PI / 4.0)
+        );
+    }
+}
@@ -112,6 +112,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_cast)]
     fn computing_vector_magnitudes() {
         assert_eq!(Vector::new(1, 0, 0).mag(), 1.0);
         assert_eq!(Vector::new(0, 1, 0).mag(), 1.0);
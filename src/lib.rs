@@ -2,9 +2,15 @@ pub mod geometry {
     pub mod matrix;
     pub use matrix::Matrix;
 
+    mod motion;
+    pub use motion::Motion;
+
     pub mod point;
     pub use point::Point;
 
+    mod quaternion;
+    pub use quaternion::Quaternion;
+
     mod tuple;
     pub use tuple::Tuple;
 
@@ -16,8 +22,17 @@ pub mod geometry {
 }
 
 pub mod raytracer {
+    mod adaptive;
+    pub use adaptive::AdaptiveSampling;
+
+    mod aov;
+    pub use aov::{Aov, RenderOutput};
+
+    mod brdf;
+    pub use brdf::{BlinnPhong, Brdf, Lambert, OrenNayar, Phong, Shading, Toon};
+
     mod camera;
-    pub use camera::Camera;
+    pub use camera::{Camera, Projection};
 
     mod canvas;
     pub use canvas::Canvas;
@@ -25,6 +40,17 @@ pub mod raytracer {
     pub mod color;
     pub use color::Color;
 
+    mod denoise;
+    pub use denoise::Denoiser;
+
+    mod environment;
+    pub use environment::Environment;
+
+    mod hdr;
+
+    mod integrator;
+    pub use integrator::Integrator;
+
     mod intersection;
     pub use intersection::{Intersection, IntersectionState, Intersections};
 
@@ -34,17 +60,47 @@ pub mod raytracer {
     mod material;
     pub use material::Material;
 
+    mod microfacet;
+    pub use microfacet::Microfacet;
+
+    mod occlusion;
+    pub use occlusion::AmbientOcclusion;
+
+    mod pick;
+    pub use pick::Pick;
+
+    mod post_process;
+    pub use post_process::{PostProcess, ToneMap};
+
+    mod ppm;
+    pub use ppm::{PpmError, PpmFormat};
+
     mod ray;
     pub use ray::Ray;
 
+    pub mod sampling;
+    pub use sampling::Rng;
+
+    mod sky;
+    pub use sky::Sky;
+
+    mod stereo;
+    pub use stereo::{Convergence, StereoCamera, StereoPacking};
+
+    mod volume;
+    pub use volume::{Fog, Medium};
+
     mod world;
-    pub use world::World;
+    pub use world::{LightId, ObjectId, World};
 }
 
 pub mod shape {
     mod base;
     pub use base::Shape;
 
+    mod bump;
+    pub use bump::{Bump, BumpFunction, Noise};
+
     mod sphere;
     pub use sphere::Sphere;
 }
@@ -0,0 +1,163 @@
+use crate::raytracer::{color, Color};
+
+/// The number of standard errors either side of the mean that make up a 95%
+/// confidence interval.
+const Z_95: f64 = 1.96;
+
+/// The number of samples a pixel needs before its variance can be trusted;
+/// a couple of samples can agree by chance, even in a noisy pixel.
+const MIN_SAMPLES: usize = 4;
+
+/// Sampling each pixel only as often as it needs: pixels whose samples all
+/// agree, such as those in large flat regions, stop early, while noisy ones
+/// keep being sampled, up to a limit.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AdaptiveSampling {
+    threshold: f64,
+    max_samples: usize,
+}
+
+impl AdaptiveSampling {
+    /// A pixel is done once the 95% confidence interval of its mean
+    /// luminance is within `threshold` of the mean. The threshold is
+    /// absolute for pixels darker than 1, and relative to the mean for
+    /// brighter ones. No pixel is done with fewer than four samples, or takes
+    /// more than `max_samples`.
+    pub fn new(threshold: f64, max_samples: usize) -> AdaptiveSampling {
+        AdaptiveSampling {
+            threshold,
+            max_samples,
+        }
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub fn max_samples(&self) -> usize {
+        self.max_samples
+    }
+
+    pub(crate) fn has_converged(&self, stats: &PixelStats) -> bool {
+        stats.count() >= MIN_SAMPLES && stats.error() <= self.threshold * stats.mean().max(1.0)
+    }
+}
+
+/// The running mean and variance of the luminance of a pixel's samples,
+/// using Welford's algorithm, along with their average color.
+#[derive(Debug, Copy, Clone, Default)]
+pub(crate) struct PixelStats {
+    count: usize,
+    sum: Color,
+    mean: f64,
+    m2: f64,
+}
+
+impl PixelStats {
+    pub(crate) fn add(&mut self, sample: Color) {
+        let x = sample.luminance();
+        self.count += 1;
+        self.sum = self.sum + sample;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The average of the samples' colors.
+    pub(crate) fn color(&self) -> Color {
+        if self.count == 0 {
+            color::BLACK
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    pub(crate) fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// The unbiased sample variance of the luminance.
+    pub(crate) fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    /// The half-width of the 95% confidence interval of the mean luminance.
+    pub(crate) fn error(&self) -> f64 {
+        Z_95 * (self.variance() / self.count as f64).sqrt()
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feq;
+
+    fn stats_of(samples: &[f64]) -> PixelStats {
+        let mut stats = PixelStats::default();
+        for &s in samples {
+            stats.add(Color::new(s, s, s));
+        }
+        stats
+    }
+
+    #[test]
+    fn tracking_the_mean_and_variance_of_samples() {
+        let stats = stats_of(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+
+        assert_eq!(stats.count(), 8);
+        assert!(feq(stats.mean(), 5.0));
+        assert!(feq(stats.variance(), 32.0 / 7.0));
+        assert!(feq(stats.error(), 1.96 * (32.0f64 / 7.0 / 8.0).sqrt()));
+    }
+
+    #[test]
+    fn the_pixel_color_is_the_average_of_its_samples() {
+        let mut stats = PixelStats::default();
+        stats.add(Color::new(1, 0, 0));
+        stats.add(Color::new(0, 0, 1));
+
+        assert_eq!(stats.color(), Color::new(0.5, 0, 0.5));
+    }
+
+    #[test]
+    fn a_few_samples_never_converge() {
+        let adaptive = AdaptiveSampling::new(0.1, 64);
+
+        assert!(!adaptive.has_converged(&stats_of(&[0.5])));
+        assert!(!adaptive.has_converged(&stats_of(&[0.5, 0.5, 0.5])));
+    }
+
+    #[test]
+    fn samples_that_agree_converge() {
+        let adaptive = AdaptiveSampling::new(0.01, 64);
+
+        assert!(adaptive.has_converged(&stats_of(&[0.5, 0.5, 0.5, 0.5])));
+    }
+
+    #[test]
+    fn noisy_samples_need_more_samples_to_converge() {
+        let adaptive = AdaptiveSampling::new(0.1, 64);
+        let noisy: Vec<f64> = (0..128).map(|i| (i % 2) as f64).collect();
+
+        assert!(!adaptive.has_converged(&stats_of(&noisy[..4])));
+        assert!(adaptive.has_converged(&stats_of(&noisy)));
+    }
+
+    #[test]
+    fn bright_pixels_converge_relative_to_their_brightness() {
+        let adaptive = AdaptiveSampling::new(0.1, 64);
+
+        assert!(!adaptive.has_converged(&stats_of(&[0.0, 1.0, 0.0, 1.0])));
+        assert!(adaptive.has_converged(&stats_of(&[10.0, 11.0, 10.0, 11.0])));
+    }
+}
@@ -0,0 +1,338 @@
+use crate::geometry::Tuple;
+use crate::raytracer::{color, Camera, Canvas, Color, LightId, ObjectId, World};
+
+/// An arbitrary output variable: a buffer rendered alongside the final
+/// image, describing what the camera sees at each pixel.
+///
+/// Each is taken from the ray through the center of the pixel, and describes
+/// the nearest surface it hits, looking through any volumes, even when the
+/// final image is sampled with many rays.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Aov {
+    /// The distance along the pixel's ray to the hit, in every channel.
+    /// Pixels that see nothing are infinitely far away.
+    Depth,
+    /// The world-space surface normal, facing the camera, with x, y and z
+    /// stored as red, green and blue. Pixels that see nothing are black.
+    Normal,
+    /// The color of the hit object's material, before any lighting.
+    Albedo,
+    /// The handle of the hit object.
+    ObjectId,
+    /// The light each of the world's lights contributes to the surface, one
+    /// buffer per light. These exclude emission, and any fog or volumes in
+    /// front of the surface.
+    Lights,
+    /// The number of rays traced for the pixel, in every channel. This only
+    /// varies between pixels with adaptive sampling.
+    SampleCount,
+}
+
+/// A rendered image, and the output variables requested with it.
+pub struct RenderOutput {
+    beauty: Canvas,
+    depth: Option<Canvas>,
+    normal: Option<Canvas>,
+    albedo: Option<Canvas>,
+    object_ids: Option<Vec<Option<ObjectId>>>,
+    lights: Vec<(LightId, Canvas)>,
+    sample_counts: Option<Canvas>,
+}
+
+impl RenderOutput {
+    /// Wraps an image rendered elsewhere, such as one loaded from a file,
+    /// so that it can be given output variables with the `with_*` methods.
+    pub fn new(beauty: Canvas) -> RenderOutput {
+        RenderOutput {
+            beauty,
+            depth: None,
+            normal: None,
+            albedo: None,
+            object_ids: None,
+            lights: vec![],
+            sample_counts: None,
+        }
+    }
+
+    pub fn with_depth(mut self, depth: Canvas) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn with_normal(mut self, normal: Canvas) -> Self {
+        self.normal = Some(normal);
+        self
+    }
+
+    pub fn with_albedo(mut self, albedo: Canvas) -> Self {
+        self.albedo = Some(albedo);
+        self
+    }
+
+    fn for_render(width: usize, height: usize, world: &World, aovs: &[Aov]) -> RenderOutput {
+        let canvas_for = |aov| aovs.contains(&aov).then(|| Canvas::new(width, height));
+        let lights = if aovs.contains(&Aov::Lights) {
+            world
+                .lights()
+                .map(|(id, _)| (id, Canvas::new(width, height)))
+                .collect()
+        } else {
+            vec![]
+        };
+
+        RenderOutput {
+            beauty: Canvas::new(width, height),
+            depth: canvas_for(Aov::Depth),
+            normal: canvas_for(Aov::Normal),
+            albedo: canvas_for(Aov::Albedo),
+            object_ids: aovs
+                .contains(&Aov::ObjectId)
+                .then(|| vec![None; width * height]),
+            lights,
+            sample_counts: canvas_for(Aov::SampleCount),
+        }
+    }
+
+    /// The final, shaded image.
+    pub fn beauty(&self) -> &Canvas {
+        &self.beauty
+    }
+
+    pub fn depth(&self) -> Option<&Canvas> {
+        self.depth.as_ref()
+    }
+
+    pub fn normal(&self) -> Option<&Canvas> {
+        self.normal.as_ref()
+    }
+
+    pub fn albedo(&self) -> Option<&Canvas> {
+        self.albedo.as_ref()
+    }
+
+    /// The handle of the object visible at a pixel, or `None` if there's no
+    /// object there or object IDs weren't requested.
+    pub fn object_id(&self, col: usize, row: usize) -> Option<ObjectId> {
+        let ids = self.object_ids.as_ref()?;
+        ids[row * self.beauty.width() + col]
+    }
+
+    /// A matte for compositing: white wherever the given object is visible,
+    /// and black elsewhere. Returns `None` if object IDs weren't requested.
+    pub fn object_mask(&self, id: ObjectId) -> Option<Canvas> {
+        self.object_ids.as_ref()?;
+        let mut mask = Canvas::new(self.beauty.width(), self.beauty.height());
+        for row in 0..mask.height() {
+            for col in 0..mask.width() {
+                if self.object_id(col, row) == Some(id) {
+                    mask.set(col, row, color::WHITE);
+                }
+            }
+        }
+        Some(mask)
+    }
+
+    /// The light contributed by the given light, if light contributions
+    /// were requested.
+    pub fn light(&self, id: LightId) -> Option<&Canvas> {
+        self.lights
+            .iter()
+            .find(|(it, _)| *it == id)
+            .map(|(_, canvas)| canvas)
+    }
+
+    /// Iterates over the light contributions, in the world's light order.
+    pub fn lights(&self) -> impl Iterator<Item = (LightId, &Canvas)> {
+        self.lights.iter().map(|(id, canvas)| (*id, canvas))
+    }
+
+    pub fn sample_counts(&self) -> Option<&Canvas> {
+        self.sample_counts.as_ref()
+    }
+}
+
+impl Camera {
+    /// Renders the world, along with the requested output variables.
+    pub fn render_aovs(&self, world: &World, aovs: &[Aov]) -> RenderOutput {
+        let mut output = RenderOutput::for_render(self.hsize(), self.vsize(), world, aovs);
+
+        for y in 0..self.vsize() {
+            for x in 0..self.hsize() {
+                let (color, samples) = self.sample_pixel(world, x, y);
+                output.beauty.set(x, y, color);
+                if let Some(counts) = output.sample_counts.as_mut() {
+                    let n = samples as f64;
+                    counts.set(x, y, Color::new(n, n, n));
+                }
+                if let Some(depth) = output.depth.as_mut() {
+                    let far = f64::INFINITY;
+                    depth.set(x, y, Color::new(far, far, far));
+                }
+                if !self.covers_pixel(x, y) {
+                    continue;
+                }
+
+                // the outputs describe the surface behind any volumes
+                let mut ray = self.ray_for_pixel(x, y);
+                let state = loop {
+                    let xs = world.intersect(ray);
+                    let hit = match xs.hit() {
+                        Some(hit) => hit,
+                        None => break None,
+                    };
+                    match hit.object().material().medium() {
+                        Some(medium) => ray = world.volume_segment(ray, hit, medium).rest,
+                        None => break Some(hit.compute_state(ray)),
+                    }
+                };
+                let state = match state {
+                    Some(state) => state,
+                    None => continue,
+                };
+
+                if let Some(depth) = output.depth.as_mut() {
+                    depth.set(x, y, Color::new(state.t(), state.t(), state.t()));
+                }
+                if let Some(normal) = output.normal.as_mut() {
+                    let n = state.normal_v();
+                    normal.set(x, y, Color::new(n.x(), n.y(), n.z()));
+                }
+                if let Some(albedo) = output.albedo.as_mut() {
+                    albedo.set(x, y, state.object().material().color());
+                }
+                if let Some(ids) = output.object_ids.as_mut() {
+                    ids[y * self.hsize() + x] = world.id_of(state.object());
+                }
+                if !output.lights.is_empty() {
+                    for (id, color) in world.light_contributions(&state) {
+                        if let Some((_, canvas)) =
+                            output.lights.iter_mut().find(|(it, _)| *it == id)
+                        {
+                            canvas.set(x, y, color);
+                        }
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::{view_transform, Matrix, Point, Vector},
+        raytracer::{world::test_utils::default_world, Material, Medium, PointLight},
+        shape::Sphere,
+    };
+    use std::f64::consts::PI;
+
+    fn camera() -> Camera {
+        let from = Point::new(0, 0, -5);
+        let to = Point::new(0, 0, 0);
+        let up = Vector::new(0, 1, 0);
+        Camera::new(11, 11, PI / 2.0).with_transform(view_transform(from, to, up))
+    }
+
+    #[test]
+    fn only_the_requested_outputs_are_rendered() {
+        let w = default_world();
+
+        let output = camera().render_aovs(&w, &[Aov::Depth]);
+
+        assert!(output.depth().is_some());
+        assert!(output.normal().is_none());
+        assert!(output.albedo().is_none());
+        assert_eq!(output.object_id(5, 5), None);
+        assert_eq!(output.lights().count(), 0);
+    }
+
+    #[test]
+    fn the_beauty_image_matches_a_plain_render() {
+        let w = default_world();
+        let c = camera();
+
+        let output = c.render_aovs(&w, &[Aov::Depth, Aov::Lights]);
+
+        assert_eq!(output.beauty().get(5, 5), c.render(&w).get(5, 5));
+    }
+
+    #[test]
+    fn outputs_describe_the_surface_seen_at_each_pixel() {
+        let w = default_world();
+        let (outer, _) = w.objects().next().unwrap();
+
+        let output =
+            camera().render_aovs(&w, &[Aov::Depth, Aov::Normal, Aov::Albedo, Aov::ObjectId]);
+
+        assert_eq!(output.depth().unwrap().get(5, 5), Color::new(4, 4, 4));
+        assert_eq!(output.normal().unwrap().get(5, 5), Color::new(0, 0, -1));
+        assert_eq!(
+            output.albedo().unwrap().get(5, 5),
+            Color::new(0.8, 1.0, 0.6)
+        );
+        assert_eq!(output.object_id(5, 5), Some(outer));
+    }
+
+    #[test]
+    fn outputs_describe_the_surface_behind_a_volume() {
+        let mut w = default_world();
+        let (outer, _) = w.objects().next().unwrap();
+        w.add_object(
+            Sphere::new()
+                .with_transform(Matrix::new().scale_u(0.5).translate(0, 0, -3))
+                .with_material(Material::new().with_medium(Medium::default())),
+        );
+
+        let output = camera().render_aovs(&w, &[Aov::Depth, Aov::Normal, Aov::ObjectId]);
+
+        assert_eq!(output.depth().unwrap().get(5, 5), Color::new(4, 4, 4));
+        assert_eq!(output.normal().unwrap().get(5, 5), Color::new(0, 0, -1));
+        assert_eq!(output.object_id(5, 5), Some(outer));
+    }
+
+    #[test]
+    fn pixels_that_see_nothing_are_infinitely_far_away() {
+        let w = default_world();
+
+        let output = camera().render_aovs(&w, &[Aov::Depth, Aov::Normal, Aov::ObjectId]);
+
+        assert!(output.depth().unwrap().get(0, 0).red().is_infinite());
+        assert_eq!(output.normal().unwrap().get(0, 0), color::BLACK);
+        assert_eq!(output.object_id(0, 0), None);
+    }
+
+    #[test]
+    fn an_object_mask_covers_the_pixels_where_the_object_is_visible() {
+        let w = default_world();
+        let (outer, _) = w.objects().next().unwrap();
+
+        let output = camera().render_aovs(&w, &[Aov::ObjectId]);
+        let mask = output.object_mask(outer).unwrap();
+
+        assert_eq!(mask.get(5, 5), color::WHITE);
+        assert_eq!(mask.get(0, 0), color::BLACK);
+    }
+
+    #[test]
+    fn light_contributions_add_up_to_the_beauty_image() {
+        let mut w = default_world();
+        let key = w.lights().next().unwrap().0;
+        let fill = w.add_light(PointLight::new(
+            Point::new(10, 10, -10),
+            Color::new(0.5, 0.5, 0.5),
+        ));
+
+        let output = camera().render_aovs(&w, &[Aov::Lights]);
+
+        let key = output.light(key).unwrap();
+        let fill = output.light(fill).unwrap();
+        for (x, y) in [(5, 5), (3, 7), (8, 2)] {
+            assert_eq!(key.get(x, y) + fill.get(x, y), output.beauty().get(x, y));
+        }
+        assert_ne!(key.get(5, 5), fill.get(5, 5));
+    }
+}
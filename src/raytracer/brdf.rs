@@ -0,0 +1,492 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+use crate::geometry::Vector;
+use crate::raytracer::{color, sampling, Color, Material, Microfacet, Rng};
+
+/// A model of how a surface reflects light: a bidirectional reflectance
+/// distribution function. Implement this to add shading models of your own;
+/// see `Shading::Custom`.
+///
+/// All vectors are normalized, and point away from the surface.
+pub trait Brdf: fmt::Debug + Sync {
+    /// Returns how much of the light arriving from `light_v` is reflected
+    /// towards `eye_v`, per unit solid angle, for a surface with the given
+    /// material. This is what sampling integrators see.
+    fn eval(&self, material: &Material, normal_v: Vector, eye_v: Vector, light_v: Vector) -> Color;
+
+    /// Returns the light reflected towards `eye_v` by Whitted shading, from
+    /// a point light of unit intensity in the direction `light_v`. Point
+    /// light intensities are scaled by PI, so that a Lambertian surface with
+    /// a `diffuse` of 1 reflects all of the light falling on it head on, as
+    /// in the classic Phong model. Models with terms that aren't physically
+    /// plausible, such as Phong's highlight, add them here, where sampling
+    /// integrators don't see them.
+    fn shade(
+        &self,
+        material: &Material,
+        normal_v: Vector,
+        eye_v: Vector,
+        light_v: Vector,
+    ) -> Color {
+        self.eval(material, normal_v, eye_v, light_v) * (PI * light_v.dot(normal_v).max(0.0))
+    }
+
+    /// Picks a direction to follow light back along from the surface, for
+    /// light leaving towards `eye_v`. Returns the direction and the factor
+    /// to scale the light arriving from it by (the BRDF times the cosine
+    /// term, divided by the probability density of the direction), or None
+    /// if the path should end. By default, directions are cosine-weighted.
+    fn sample(
+        &self,
+        material: &Material,
+        normal_v: Vector,
+        eye_v: Vector,
+        rng: &mut Rng,
+    ) -> Option<(Vector, Color)> {
+        let light_v = sampling::cosine_hemisphere(normal_v, rng);
+        Some((light_v, self.eval(material, normal_v, eye_v, light_v) * PI))
+    }
+}
+
+/// How a material reflects the light that falls on it.
+#[derive(Debug, Copy, Clone)]
+pub enum Shading {
+    Phong,
+    BlinnPhong,
+    Lambert,
+    OrenNayar(OrenNayar),
+    Toon(Toon),
+    Microfacet(Microfacet),
+    /// A shading model defined outside the crate.
+    Custom(&'static dyn Brdf),
+}
+
+impl Shading {
+    fn brdf(&self) -> &dyn Brdf {
+        match self {
+            Shading::Phong => &Phong,
+            Shading::BlinnPhong => &BlinnPhong,
+            Shading::Lambert => &Lambert,
+            Shading::OrenNayar(brdf) => brdf,
+            Shading::Toon(brdf) => brdf,
+            Shading::Microfacet(brdf) => brdf,
+            Shading::Custom(brdf) => *brdf,
+        }
+    }
+}
+
+impl Brdf for Shading {
+    fn eval(&self, material: &Material, normal_v: Vector, eye_v: Vector, light_v: Vector) -> Color {
+        self.brdf().eval(material, normal_v, eye_v, light_v)
+    }
+
+    fn shade(
+        &self,
+        material: &Material,
+        normal_v: Vector,
+        eye_v: Vector,
+        light_v: Vector,
+    ) -> Color {
+        self.brdf().shade(material, normal_v, eye_v, light_v)
+    }
+
+    fn sample(
+        &self,
+        material: &Material,
+        normal_v: Vector,
+        eye_v: Vector,
+        rng: &mut Rng,
+    ) -> Option<(Vector, Color)> {
+        self.brdf().sample(material, normal_v, eye_v, rng)
+    }
+}
+
+// Custom models are equal if they're the same object.
+impl PartialEq for Shading {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Shading::Phong, Shading::Phong) => true,
+            (Shading::BlinnPhong, Shading::BlinnPhong) => true,
+            (Shading::Lambert, Shading::Lambert) => true,
+            (Shading::OrenNayar(a), Shading::OrenNayar(b)) => a == b,
+            (Shading::Toon(a), Shading::Toon(b)) => a == b,
+            (Shading::Microfacet(a), Shading::Microfacet(b)) => a == b,
+            (Shading::Custom(a), Shading::Custom(b)) => std::ptr::addr_eq(*a, *b),
+            _ => false,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// The classic Phong model, using the material's `diffuse`, `specular` and
+/// `shininess`. Sampling integrators only see its diffuse part.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Phong;
+
+impl Brdf for Phong {
+    fn eval(&self, material: &Material, normal_v: Vector, eye_v: Vector, light_v: Vector) -> Color {
+        Lambert.eval(material, normal_v, eye_v, light_v)
+    }
+
+    fn shade(
+        &self,
+        material: &Material,
+        normal_v: Vector,
+        eye_v: Vector,
+        light_v: Vector,
+    ) -> Color {
+        let light_dot_normal = light_v.dot(normal_v);
+        if light_dot_normal < 0.0 {
+            return color::BLACK;
+        }
+
+        let reflect_dot_eye = (-light_v).reflect(normal_v).dot(eye_v);
+        let specular = if reflect_dot_eye > 0.0 {
+            material.specular() * reflect_dot_eye.powf(material.shininess())
+        } else {
+            0.0
+        };
+
+        lambert(material) * light_dot_normal + color::WHITE * specular
+    }
+}
+
+/// Phong's model, with the highlight computed from the angle between the
+/// normal and the half-way vector between the eye and the light. This gives
+/// wider highlights than Phong for the same `shininess`, which look more
+/// natural at grazing angles. Sampling integrators only see its diffuse
+/// part.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BlinnPhong;
+
+impl Brdf for BlinnPhong {
+    fn eval(&self, material: &Material, normal_v: Vector, eye_v: Vector, light_v: Vector) -> Color {
+        Lambert.eval(material, normal_v, eye_v, light_v)
+    }
+
+    fn shade(
+        &self,
+        material: &Material,
+        normal_v: Vector,
+        eye_v: Vector,
+        light_v: Vector,
+    ) -> Color {
+        let light_dot_normal = light_v.dot(normal_v);
+        if light_dot_normal < 0.0 {
+            return color::BLACK;
+        }
+
+        let half_v = (eye_v + light_v).norm();
+        let normal_dot_half = normal_v.dot(half_v);
+        let specular = if normal_dot_half > 0.0 {
+            material.specular() * normal_dot_half.powf(material.shininess())
+        } else {
+            0.0
+        };
+
+        lambert(material) * light_dot_normal + color::WHITE * specular
+    }
+}
+
+/// A perfectly matte surface, that reflects light equally in all directions,
+/// using the material's `diffuse`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Lambert;
+
+impl Brdf for Lambert {
+    fn eval(
+        &self,
+        material: &Material,
+        normal_v: Vector,
+        _eye_v: Vector,
+        light_v: Vector,
+    ) -> Color {
+        if light_v.dot(normal_v) <= 0.0 {
+            return color::BLACK;
+        }
+
+        lambert(material) / PI
+    }
+}
+
+/// A rough matte surface, such as clay, plaster or the moon, that looks
+/// flatter than a Lambertian one since it reflects more light back towards
+/// the light source.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OrenNayar {
+    sigma: f64,
+}
+
+impl OrenNayar {
+    /// Takes the standard deviation of the angle of the surface's
+    /// microfacets, in radians. A sigma of 0 is Lambertian.
+    pub fn new(sigma: f64) -> OrenNayar {
+        OrenNayar { sigma }
+    }
+
+    pub fn sigma(&self) -> f64 {
+        self.sigma
+    }
+}
+
+impl Brdf for OrenNayar {
+    fn eval(&self, material: &Material, normal_v: Vector, eye_v: Vector, light_v: Vector) -> Color {
+        let cos_i = light_v.dot(normal_v);
+        let cos_o = eye_v.dot(normal_v);
+        if cos_i <= 0.0 {
+            return color::BLACK;
+        }
+        let cos_o = cos_o.clamp(0.0, 1.0);
+
+        let s2 = self.sigma * self.sigma;
+        let a = 1.0 - 0.5 * s2 / (s2 + 0.33);
+        let b = 0.45 * s2 / (s2 + 0.09);
+
+        // the cosine of the azimuthal angle between the light and the eye
+        let light_t = light_v - normal_v * cos_i;
+        let eye_t = eye_v - normal_v * cos_o;
+        let cos_phi = if light_t.mag() > 0.0 && eye_t.mag() > 0.0 {
+            light_t.norm().dot(eye_t.norm()).max(0.0)
+        } else {
+            0.0
+        };
+
+        // sin(alpha) * tan(beta), where alpha is the larger of the two
+        // angles to the normal, and beta the smaller
+        let sin_i = (1.0 - cos_i * cos_i).max(0.0).sqrt();
+        let sin_o = (1.0 - cos_o * cos_o).max(0.0).sqrt();
+        let sin_alpha_tan_beta = if cos_i > cos_o {
+            sin_o * sin_i / cos_i
+        } else {
+            sin_i * sin_o / cos_o.max(f64::MIN_POSITIVE)
+        };
+
+        lambert(material) * ((a + b * cos_phi * sin_alpha_tan_beta) / PI)
+    }
+}
+
+/// Cel shading, for a flat, illustrated look: the diffuse term is quantized
+/// into a number of bands of constant brightness, and the highlight is
+/// either on or off. Sampling integrators see it as a Lambertian surface.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Toon {
+    bands: u32,
+}
+
+impl Toon {
+    pub fn new(bands: u32) -> Toon {
+        Toon {
+            bands: bands.max(1),
+        }
+    }
+
+    pub fn bands(&self) -> u32 {
+        self.bands
+    }
+}
+
+impl Brdf for Toon {
+    fn eval(&self, material: &Material, normal_v: Vector, eye_v: Vector, light_v: Vector) -> Color {
+        Lambert.eval(material, normal_v, eye_v, light_v)
+    }
+
+    fn shade(
+        &self,
+        material: &Material,
+        normal_v: Vector,
+        eye_v: Vector,
+        light_v: Vector,
+    ) -> Color {
+        let light_dot_normal = light_v.dot(normal_v);
+        if light_dot_normal <= 0.0 {
+            return color::BLACK;
+        }
+
+        let bands = self.bands as f64;
+        let diffuse = (light_dot_normal * bands).ceil() / bands;
+
+        let half_v = (eye_v + light_v).norm();
+        let highlight = normal_v.dot(half_v).max(0.0).powf(material.shininess());
+        let specular = if highlight > 0.5 {
+            material.specular()
+        } else {
+            0.0
+        };
+
+        lambert(material) * diffuse + color::WHITE * specular
+    }
+}
+
+// The light reflected by the diffuse part of the material.
+fn lambert(material: &Material) -> Color {
+    material.color() * material.diffuse()
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Point;
+    use crate::raytracer::PointLight;
+
+    // Lights the origin of a surface facing -z, with the eye and light
+    // along the given vectors, without ambient light.
+    fn light(shading: Shading, eye_v: Vector, light_v: Vector) -> Color {
+        let m = Material::new().with_ambient(0.0).with_shading(shading);
+        let light = PointLight::new(Point::new(0, 0, 0) + light_v * 10, color::WHITE);
+
+        m.lighting(
+            light,
+            Point::new(0, 0, 0),
+            eye_v,
+            Vector::new(0, 0, -1),
+            false,
+        )
+    }
+
+    #[derive(Debug)]
+    struct Flat;
+
+    impl Brdf for Flat {
+        fn eval(&self, material: &Material, _: Vector, _: Vector, _: Vector) -> Color {
+            material.color() / PI
+        }
+    }
+
+    static FLAT: Flat = Flat;
+
+    #[test]
+    fn blinn_phong_highlights_are_wider_than_phong_highlights() {
+        let eye_v = Vector::new(0, 0, -1);
+        let light_v = Vector::new(0, 0.2, -1).norm();
+
+        let phong = light(Shading::Phong, eye_v, light_v);
+        let blinn = light(Shading::BlinnPhong, eye_v, light_v);
+
+        assert!(blinn.red() > phong.red());
+    }
+
+    #[test]
+    fn blinn_phong_with_the_eye_in_the_path_of_the_reflection_vector() {
+        let eye_v = Vector::new(0, -1, -1).norm();
+        let light_v = Vector::new(0, 1, -1).norm();
+
+        let result = light(Shading::BlinnPhong, eye_v, light_v);
+
+        // the half-way vector is the normal, so the highlight is at full
+        // strength
+        assert_eq!(result, Color::new(1.5364, 1.5364, 1.5364));
+    }
+
+    #[test]
+    fn lambert_has_no_highlight() {
+        let eye_v = Vector::new(0, 0, -1);
+
+        let result = light(Shading::Lambert, eye_v, eye_v);
+
+        assert_eq!(result, Color::new(0.9, 0.9, 0.9));
+    }
+
+    #[test]
+    fn oren_nayar_with_no_roughness_is_lambertian() {
+        let eye_v = Vector::new(0, 1, -1).norm();
+        let light_v = Vector::new(1, 0, -2).norm();
+
+        let rough = light(Shading::OrenNayar(OrenNayar::new(0.0)), eye_v, light_v);
+        let lambert = light(Shading::Lambert, eye_v, light_v);
+
+        assert_eq!(rough, lambert);
+    }
+
+    #[test]
+    fn rough_surfaces_reflect_more_light_back_towards_the_light() {
+        let m = Material::new();
+        let n = Vector::new(0, 0, 1);
+        let light_v = Vector::new(1, 0, 1).norm();
+        let oren_nayar = OrenNayar::new(0.5);
+
+        let back = oren_nayar.eval(&m, n, light_v, light_v);
+        let forward = oren_nayar.eval(&m, n, Vector::new(-1, 0, 1).norm(), light_v);
+
+        assert!(back.red() > forward.red());
+        assert!(back.red() > Lambert.eval(&m, n, light_v, light_v).red());
+    }
+
+    #[test]
+    fn toon_shading_bands_the_diffuse_light() {
+        let eye_v = Vector::new(0, 0, -1);
+        let toon = Shading::Toon(Toon::new(3));
+
+        let a = light(toon, eye_v, Vector::new(0, 0.7, -1).norm());
+        let b = light(toon, eye_v, Vector::new(0, 0.8, -1).norm());
+        let c = light(toon, eye_v, Vector::new(0, 5, -1).norm());
+
+        // both cosines are in the top band
+        assert_eq!(a, b);
+        assert_eq!(a, Color::new(0.9, 0.9, 0.9));
+        assert_eq!(c, Color::new(0.3, 0.3, 0.3));
+    }
+
+    #[test]
+    fn toon_highlights_are_either_on_or_off() {
+        let eye_v = Vector::new(0, 0, -1);
+        let toon = Shading::Toon(Toon::new(2));
+
+        let result = light(toon, eye_v, eye_v);
+
+        assert_eq!(result, Color::new(1.8, 1.8, 1.8));
+    }
+
+    #[test]
+    fn custom_shading_models_can_be_plugged_in() {
+        let m = Material::new()
+            .with_color(Color::new(0.5, 0.25, 1))
+            .with_shading(Shading::Custom(&FLAT));
+        let n = Vector::new(0, 0, 1);
+
+        assert_eq!(m.brdf(n, n, n) * PI, Color::new(0.5, 0.25, 1));
+        assert_eq!(m.shading(), Shading::Custom(&FLAT));
+        assert_ne!(m.shading(), Shading::Phong);
+    }
+
+    #[test]
+    fn sampling_integrators_only_see_the_diffuse_part_of_phong_models() {
+        let m = Material::new();
+        let n = Vector::new(0, 0, 1);
+        let mut rng = Rng::new(9);
+
+        for shading in [
+            Shading::Phong,
+            Shading::BlinnPhong,
+            Shading::Toon(Toon::new(3)),
+        ] {
+            let m = m.with_shading(shading);
+            for eye_v in [
+                n,
+                Vector::new(0, 1, 0.1).norm(),
+                Vector::new(1, 0, 0.02).norm(),
+            ] {
+                for _ in 0..100 {
+                    let (_, weight) = m.sample_brdf(n, eye_v, &mut rng).unwrap();
+                    assert_eq!(weight, Color::new(0.9, 0.9, 0.9));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_default_sampling_is_consistent_with_the_brdf() {
+        let m = Material::new().with_shading(Shading::OrenNayar(OrenNayar::new(0.3)));
+        let n = Vector::new(0, 0, 1);
+        let eye_v = Vector::new(0, 1, 1).norm();
+        let mut rng = Rng::new(5);
+
+        for _ in 0..100 {
+            let (light_v, weight) = m.sample_brdf(n, eye_v, &mut rng).unwrap();
+            assert_eq!(weight, m.brdf(n, eye_v, light_v) * PI);
+        }
+    }
+}
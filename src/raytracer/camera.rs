@@ -1,7 +1,29 @@
-use crate::geometry::{view_transform, Matrix, Point, Vector};
+use std::f64::consts::PI;
 
-use super::{Canvas, Ray, World};
+use crate::geometry::{point, view_transform, Matrix, Point, Vector};
 
+use super::adaptive::PixelStats;
+use super::{color, sampling, AdaptiveSampling, Canvas, Color, Integrator, Pick, Ray, Rng, World};
+
+/// How a camera maps points on its canvas to rays.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection {
+    /// Rays fan out from the camera's position, so distant objects appear
+    /// smaller.
+    Perspective,
+    /// Rays leave the canvas in parallel, so objects appear the same size
+    /// regardless of their distance.
+    Orthographic,
+    /// The canvas spans 360 degrees of longitude horizontally and 180
+    /// degrees of latitude vertically, as in a lat-long environment map.
+    Equirectangular,
+    /// An equidistant fisheye: the distance from the center of the canvas
+    /// is proportional to the angle from the line of sight. The image circle
+    /// fills the shorter side of the canvas, and covers the field of view.
+    Fisheye { field_of_view: f64 },
+}
+
+#[derive(Debug, Clone)]
 pub struct Camera {
     hsize: usize,
     vsize: usize,
@@ -10,6 +32,15 @@ pub struct Camera {
     half_width: f64,
     half_height: f64,
     pixel_size: f64,
+    projection: Projection,
+    shift: (f64, f64),
+    aperture: f64,
+    focal_distance: f64,
+    samples: usize,
+    shutter_open: f64,
+    shutter_close: f64,
+    integrator: Integrator,
+    adaptive_sampling: Option<AdaptiveSampling>,
 }
 
 impl Camera {
@@ -26,6 +57,68 @@ impl Camera {
         } else {
             half_view
         };
+
+        Camera::from_projection(
+            hsize,
+            vsize,
+            half_width,
+            half_height,
+            Projection::Perspective,
+        )
+    }
+
+    /// Creates a camera with an orthographic projection, which sees a region
+    /// `view_height` units tall (and as wide as the aspect ratio requires)
+    /// around its line of sight.
+    pub fn orthographic(hsize: usize, vsize: usize, view_height: f64) -> Camera {
+        let aspect = (hsize as f64) / (vsize as f64);
+        let half_height = view_height / 2.0;
+        let half_width = half_height * aspect;
+
+        Camera::from_projection(
+            hsize,
+            vsize,
+            half_width,
+            half_height,
+            Projection::Orthographic,
+        )
+    }
+
+    /// Creates a camera that sees in every direction, mapping longitude and
+    /// latitude to the canvas's columns and rows. A canvas twice as wide as
+    /// it is tall gives square pixels.
+    pub fn equirectangular(hsize: usize, vsize: usize) -> Camera {
+        Camera::from_projection(hsize, vsize, PI, PI / 2.0, Projection::Equirectangular)
+    }
+
+    /// Creates a fisheye camera; a field of view of PI gives the classic
+    /// 180 degree hemispherical fisheye. Pixels outside the image circle are
+    /// rendered black.
+    pub fn fisheye(hsize: usize, vsize: usize, field_of_view: f64) -> Camera {
+        let half_view = field_of_view / 2.0;
+        let aspect = (hsize as f64) / (vsize as f64);
+        let (half_width, half_height) = if aspect >= 1.0 {
+            (half_view * aspect, half_view)
+        } else {
+            (half_view, half_view / aspect)
+        };
+
+        Camera::from_projection(
+            hsize,
+            vsize,
+            half_width,
+            half_height,
+            Projection::Fisheye { field_of_view },
+        )
+    }
+
+    fn from_projection(
+        hsize: usize,
+        vsize: usize,
+        half_width: f64,
+        half_height: f64,
+        projection: Projection,
+    ) -> Camera {
         let pixel_size = (half_width * 2.0) / (hsize as f64);
 
         Camera {
@@ -36,6 +129,15 @@ impl Camera {
             half_width,
             half_height,
             pixel_size,
+            projection,
+            shift: (0.0, 0.0),
+            aperture: 0.0,
+            focal_distance: 1.0,
+            samples: 1,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            integrator: Integrator::Whitted,
+            adaptive_sampling: None,
         }
     }
 
@@ -49,33 +151,292 @@ impl Camera {
         self.with_transform(view_transform(from, to, up))
     }
 
+    pub fn hsize(&self) -> usize {
+        self.hsize
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.vsize
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Slides the canvas within its plane without turning the camera, like a
+    /// shift lens. The shift is measured on the plane z=-1, with +x to the
+    /// left, and only affects perspective and orthographic projections.
+    pub fn with_lens_shift(mut self, x: f64, y: f64) -> Self {
+        self.shift = (x, y);
+        self
+    }
+
+    /// Sets the radius of the lens. A radius of zero (the default) gives a
+    /// pinhole camera, where everything is in focus. Orthographic cameras
+    /// have no lens, and ignore this.
+    pub fn with_aperture(mut self, aperture: f64) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    /// Sets the distance from the camera to the plane that is in perfect
+    /// focus. This has no effect unless the aperture is non-zero.
+    pub fn with_focal_distance(mut self, focal_distance: f64) -> Self {
+        self.focal_distance = focal_distance;
+        self
+    }
+
+    /// Sets the number of rays traced, and averaged, for each pixel, which is
+    /// at least one.
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// Lets the number of rays traced for each pixel vary with how noisy the
+    /// pixel is. Every pixel is first given the camera's `samples` (and at
+    /// least four, even with the default of one), then keeps being sampled
+    /// until its color has converged, or it reaches the maximum.
+    pub fn with_adaptive_sampling(mut self, adaptive_sampling: AdaptiveSampling) -> Self {
+        self.adaptive_sampling = Some(adaptive_sampling);
+        self
+    }
+
+    pub fn adaptive_sampling(&self) -> Option<AdaptiveSampling> {
+        self.adaptive_sampling
+    }
+
+    /// Sets the interval during which the shutter is open. Each sampled ray
+    /// is given a random time in `[open, close)`, so that objects moving in
+    /// that interval are blurred. Times are on the same scale as `Motion`,
+    /// where objects move between time 0 and time 1.
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    /// Returns false if the center of the pixel lies outside a fisheye's
+    /// image circle.
+    pub fn covers_pixel(&self, px: usize, py: usize) -> bool {
+        self.covers((px as f64) + 0.5, (py as f64) + 0.5)
+    }
+
+    /// Chooses the algorithm used to compute the color seen along each ray.
+    pub fn with_integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+
     pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
-        // the offset from the edge of the canvas to the pixel's center
-        let xoffset = ((px as f64) + 0.5) * self.pixel_size;
-        let yoffset = ((py as f64) + 0.5) * self.pixel_size;
+        self.ray_through((px as f64) + 0.5, (py as f64) + 0.5, (0.0, 0.0))
+            .with_time(self.shutter_open)
+    }
 
-        // the untransformed coordinates of the pixel in world space
+    /// Returns a ray through a random point within the pixel, leaving from a
+    /// random point on the lens at a random time while the shutter is open.
+    /// Returns `None` if the point lies outside a fisheye's image circle.
+    pub fn sample_ray(&self, px: usize, py: usize, rng: &mut Rng) -> Option<Ray> {
+        let x = (px as f64) + rng.next_f64();
+        let y = (py as f64) + rng.next_f64();
+        let lens = sampling::concentric_disk(rng.next_f64(), rng.next_f64());
+        // an instantaneous shutter doesn't need a random time, and leaves the
+        // rest of the pixel's random sequence as it was
+        let time = if self.shutter_open == self.shutter_close {
+            self.shutter_open
+        } else {
+            self.shutter_open + rng.next_f64() * (self.shutter_close - self.shutter_open)
+        };
+
+        if self.covers(x, y) {
+            Some(self.ray_through(x, y, lens).with_time(time))
+        } else {
+            None
+        }
+    }
+
+    // Returns false for points on the canvas that the projection doesn't
+    // cover, i.e. outside a fisheye's image circle.
+    fn covers(&self, x: f64, y: f64) -> bool {
+        match self.projection {
+            Projection::Fisheye { field_of_view } => {
+                let (world_x, world_y) = self.canvas_point(x, y);
+                world_x.hypot(world_y) <= field_of_view / 2.0
+            }
+            _ => true,
+        }
+    }
+
+    // Returns the ray through the point (x, y) on the canvas, measured in
+    // pixels, leaving from the point `lens` on the unit disk (which is scaled
+    // by the aperture).
+    fn ray_through(&self, x: f64, y: f64, lens: (f64, f64)) -> Ray {
+        let (origin, target) = match self.projection {
+            Projection::Perspective => self.perspective_through(x, y, lens),
+            Projection::Orthographic => self.orthographic_through(x, y),
+            Projection::Equirectangular => self.equirectangular_through(x, y),
+            Projection::Fisheye { .. } => self.fisheye_through(x, y),
+        };
+
+        // using the camera matrix, transform both points, and then compute
+        // the ray's direction vector.
+        let origin = self.inverse_transform * origin;
+        let target = self.inverse_transform * target;
+        let direction = (target - origin).norm();
+
+        Ray::new(origin, direction).with_interval(0.0, f64::INFINITY)
+    }
+
+    // Converts a point on the canvas, measured in pixels, to the untransformed
+    // coordinates of that point on the plane z=-1. For the spherical
+    // projections, the coordinates are angles instead.
+    fn canvas_point(&self, x: f64, y: f64) -> (f64, f64) {
+        // the offset from the edge of the canvas to the point
+        let xoffset = x * self.pixel_size;
+        let yoffset = y * self.pixel_size;
+
+        // the untransformed coordinates of the point in world space
         // (note that the camera looks toward -z, so +x is to the *left*.)
         let world_x = self.half_width - xoffset;
         let world_y = self.half_height - yoffset;
+        (world_x, world_y)
+    }
+
+    fn shifted_canvas_point(&self, x: f64, y: f64) -> (f64, f64) {
+        let (world_x, world_y) = self.canvas_point(x, y);
+        (world_x + self.shift.0, world_y + self.shift.1)
+    }
+
+    // Returns the untransformed origin and target of a perspective ray.
+    fn perspective_through(&self, x: f64, y: f64, lens: (f64, f64)) -> (Point, Point) {
+        let (world_x, world_y) = self.shifted_canvas_point(x, y);
+
+        // the ray through the center of the lens crosses the canvas (at z=-1)
+        // at (world_x, world_y), and every ray through the lens converges on
+        // the same point on the focal plane.
+        let focus = Point::new(
+            world_x * self.focal_distance,
+            world_y * self.focal_distance,
+            -self.focal_distance,
+        );
+        let (lens_x, lens_y) = lens;
+        let lens = Point::new(lens_x * self.aperture, lens_y * self.aperture, 0);
+
+        (lens, focus)
+    }
+
+    // Returns the untransformed origin and target of an orthographic ray,
+    // which leaves the camera's plane (z=0) parallel to the line of sight.
+    fn orthographic_through(&self, x: f64, y: f64) -> (Point, Point) {
+        let (world_x, world_y) = self.shifted_canvas_point(x, y);
+
+        (
+            Point::new(world_x, world_y, 0),
+            Point::new(world_x, world_y, -1),
+        )
+    }
+
+    // Returns the untransformed origin and target of an equirectangular ray.
+    fn equirectangular_through(&self, x: f64, y: f64) -> (Point, Point) {
+        // longitude runs from PI at the left edge to -PI at the right, and
+        // latitude from PI/2 at the top to -PI/2 at the bottom; the center of
+        // the canvas looks down the line of sight (-z).
+        let longitude = PI - (x / self.hsize as f64) * 2.0 * PI;
+        let latitude = PI / 2.0 - (y / self.vsize as f64) * PI;
+
+        let direction = Vector::new(
+            longitude.sin() * latitude.cos(),
+            latitude.sin(),
+            -longitude.cos() * latitude.cos(),
+        );
+        (point::ORIGIN, point::ORIGIN + direction)
+    }
+
+    // Returns the untransformed origin and target of a fisheye ray.
+    fn fisheye_through(&self, x: f64, y: f64) -> (Point, Point) {
+        // for a fisheye, the canvas point is measured in radians, so its
+        // distance from the center is the angle from the line of sight.
+        let (world_x, world_y) = self.canvas_point(x, y);
+        let theta = world_x.hypot(world_y);
+        if theta == 0.0 {
+            return (point::ORIGIN, Point::new(0, 0, -1));
+        }
+
+        let direction = Vector::new(
+            theta.sin() * world_x / theta,
+            theta.sin() * world_y / theta,
+            -theta.cos(),
+        );
+        (point::ORIGIN, point::ORIGIN + direction)
+    }
+
+    // A camera with a single sample, no lens, an instantaneous shutter and a
+    // deterministic integrator traces exactly one ray through the center of
+    // each pixel.
+    fn is_pinhole(&self) -> bool {
+        self.samples == 1
+            && self.aperture == 0.0
+            && self.shutter_open == self.shutter_close
+            && !self.integrator.is_stochastic()
+            && self.adaptive_sampling.is_none()
+    }
 
-        // using the camera matrix, transform the canvas point and the origin,
-        // and then compute the ray's direction vector.
-        // (note that the canvas is at z=-1)
-        let pixel = self.inverse_transform * Point::new(world_x, world_y, -1);
-        let origin = self.inverse_transform * Point::new(0, 0, 0);
-        let direction = (pixel - origin).norm();
+    /// Returns the color of a pixel, and the number of rays traced for it.
+    pub(crate) fn sample_pixel(&self, world: &World, px: usize, py: usize) -> (Color, usize) {
+        if self.is_pinhole() {
+            let color = if self.covers_pixel(px, py) {
+                world.color_at(self.ray_for_pixel(px, py))
+            } else {
+                color::BLACK
+            };
+            return (color, 1);
+        }
 
-        Ray::new(origin, direction)
+        let max_samples = match self.adaptive_sampling {
+            Some(adaptive) => adaptive.max_samples().max(self.samples),
+            None => self.samples,
+        };
+
+        let mut rng = Rng::for_pixel(px, py);
+        let mut stats = PixelStats::default();
+        while stats.count() < max_samples {
+            if stats.count() >= self.samples
+                && self
+                    .adaptive_sampling
+                    .is_some_and(|adaptive| adaptive.has_converged(&stats))
+            {
+                break;
+            }
+            stats.add(match self.sample_ray(px, py, &mut rng) {
+                Some(ray) => self.integrator.color_at(world, ray, &mut rng),
+                None => color::BLACK,
+            });
+        }
+        (stats.color(), stats.count())
+    }
+
+    /// Reports which object, if any, is visible at the given pixel, along
+    /// with where it was hit.
+    pub fn pick(&self, world: &World, px: usize, py: usize) -> Option<Pick> {
+        if !self.covers_pixel(px, py) {
+            return None;
+        }
+        let ray = self.ray_for_pixel(px, py);
+        let xs = world.intersect(ray);
+        let hit = xs.hit()?;
+        let id = world.id_of(hit.object())?;
+        Some(Pick::new(id, &hit.compute_state(ray)))
     }
 
     pub fn render(&self, world: &World) -> Canvas {
         let mut image = Canvas::new(self.hsize, self.vsize);
         for y in 0..self.vsize {
             for x in 0..self.hsize {
-                let ray = self.ray_for_pixel(x, y);
-                let color = world.color_at(ray);
-                image.set(x, y, color);
+                image.set(x, y, self.sample_pixel(world, x, y).0);
             }
         }
         image
@@ -87,8 +448,12 @@ mod tests {
     use super::*;
     use crate::{
         feq,
-        geometry::{view_transform, Point, Vector},
-        raytracer::{world::test_utils::default_world, Color},
+        geometry::{view_transform, Point, Tuple, Vector},
+        raytracer::{
+            world::test_utils::default_world, Aov, Color, Environment, Material, PointLight,
+            Shading,
+        },
+        shape::Sphere,
     };
     use std::{f32::consts::SQRT_2, f64::consts::PI};
 
@@ -130,6 +495,16 @@ mod tests {
         assert_eq!(r.direction(), Vector::new(0, 0, -1));
     }
 
+    #[test]
+    fn a_camera_ray_only_looks_forward() {
+        let c = Camera::new(201, 101, PI / 2.0);
+
+        let r = c.ray_for_pixel(100, 50);
+
+        assert_eq!(r.t_min(), 0.0);
+        assert_eq!(r.t_max(), f64::INFINITY);
+    }
+
     #[test]
     fn constructing_a_ray_through_a_corner_of_the_canvas() {
         let c = Camera::new(201, 101, PI / 2.0);
@@ -164,4 +539,447 @@ mod tests {
 
         assert_eq!(image.get(5, 5), Color::new(0.38066, 0.47583, 0.2855));
     }
+
+    #[test]
+    fn a_camera_uses_a_perspective_projection_by_default() {
+        let c = Camera::new(160, 120, PI / 2.0);
+
+        assert_eq!(c.projection(), Projection::Perspective);
+    }
+
+    #[test]
+    fn the_pixel_size_for_an_orthographic_camera() {
+        let c = Camera::orthographic(200, 100, 4.0);
+
+        assert_eq!(c.projection(), Projection::Orthographic);
+        assert!(feq(c.pixel_size, 0.04));
+        assert!(feq(c.half_width, 4.0));
+        assert!(feq(c.half_height, 2.0));
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let c = Camera::orthographic(201, 101, 4.0);
+
+        let center = c.ray_for_pixel(100, 50);
+        let corner = c.ray_for_pixel(0, 0);
+
+        assert_eq!(center.origin(), Point::new(0, 0, 0));
+        assert_eq!(center.direction(), Vector::new(0, 0, -1));
+        assert_eq!(corner.origin(), Point::new(3.96040, 1.98020, 0));
+        assert_eq!(corner.direction(), Vector::new(0, 0, -1));
+    }
+
+    #[test]
+    fn orthographic_rays_follow_the_view_transform() {
+        let c = Camera::orthographic(201, 101, 4.0).with_view_transform(
+            Point::new(0, 0, -5),
+            Point::new(0, 0, 0),
+            Vector::new(0, 1, 0),
+        );
+
+        let r = c.ray_for_pixel(100, 0);
+
+        assert_eq!(r.origin(), Point::new(0, 1.98020, -5));
+        assert_eq!(r.direction(), Vector::new(0, 0, 1));
+    }
+
+    #[test]
+    fn rendering_with_an_orthographic_camera() {
+        let w = default_world();
+        let c = Camera::orthographic(11, 11, 2.2).with_view_transform(
+            Point::new(0, 0, -5),
+            Point::new(0, 0, 0),
+            Vector::new(0, 1, 0),
+        );
+
+        let image = c.render(&w);
+
+        // the unit sphere fills a 2x2 square, whatever its distance
+        assert_eq!(image.get(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+        assert_ne!(image.get(0, 5), color::BLACK);
+        assert_eq!(image.get(0, 0), color::BLACK);
+    }
+
+    #[test]
+    fn equirectangular_rays_cover_the_whole_sphere() {
+        let c = Camera::equirectangular(360, 180);
+
+        let center = c.ray_through(180.0, 90.0, (0.0, 0.0));
+        let left = c.ray_through(90.0, 90.0, (0.0, 0.0));
+        let right = c.ray_through(270.0, 90.0, (0.0, 0.0));
+        let behind = c.ray_through(0.0, 90.0, (0.0, 0.0));
+        let up = c.ray_through(180.0, 0.0, (0.0, 0.0));
+        let down = c.ray_through(180.0, 180.0, (0.0, 0.0));
+
+        assert_eq!(center.origin(), Point::new(0, 0, 0));
+        assert_eq!(center.direction(), Vector::new(0, 0, -1));
+        // the camera looks toward -z, so +x is to the left
+        assert_eq!(left.direction(), Vector::new(1, 0, 0));
+        assert_eq!(right.direction(), Vector::new(-1, 0, 0));
+        assert_eq!(behind.direction(), Vector::new(0, 0, 1));
+        assert_eq!(up.direction(), Vector::new(0, 1, 0));
+        assert_eq!(down.direction(), Vector::new(0, -1, 0));
+    }
+
+    #[test]
+    fn equirectangular_rays_follow_the_camera_transform() {
+        let c = Camera::equirectangular(360, 180).with_view_transform(
+            Point::new(0, 2, 0),
+            Point::new(1, 2, 0),
+            Vector::new(0, 1, 0),
+        );
+
+        let r = c.ray_through(180.0, 90.0, (0.0, 0.0));
+
+        assert_eq!(r.origin(), Point::new(0, 2, 0));
+        assert_eq!(r.direction(), Vector::new(1, 0, 0));
+    }
+
+    #[test]
+    fn fisheye_rays_are_proportional_to_the_distance_from_the_center() {
+        let c = Camera::fisheye(200, 100, PI);
+
+        let center = c.ray_through(100.0, 50.0, (0.0, 0.0));
+        let top = c.ray_through(100.0, 0.0, (0.0, 0.0));
+        let halfway = c.ray_through(75.0, 50.0, (0.0, 0.0));
+
+        assert_eq!(center.direction(), Vector::new(0, 0, -1));
+        // the edge of the image circle is 90 degrees from the line of sight
+        assert_eq!(top.direction(), Vector::new(0, 1, 0));
+        // halfway to the edge is 45 degrees (and +x is to the left)
+        assert_eq!(
+            halfway.direction(),
+            Vector::new(SQRT_2 / 2.0, 0, -SQRT_2 / 2.0)
+        );
+    }
+
+    #[test]
+    fn a_fisheye_only_covers_its_image_circle() {
+        let c = Camera::fisheye(200, 100, PI);
+
+        assert!(c.covers_pixel(100, 50));
+        assert!(c.covers_pixel(60, 50));
+        assert!(!c.covers_pixel(0, 0));
+        assert!(!c.covers_pixel(160, 50));
+    }
+
+    #[test]
+    fn rendering_with_a_fisheye_blacks_out_the_corners() {
+        let w = World::new()
+            .with_lights(vec![PointLight::new(Point::new(0, 0, 0), color::WHITE)])
+            .with_objects(vec![
+                Sphere::new().with_transform(Matrix::new().scale_u(100))
+            ]);
+        let c = Camera::fisheye(11, 11, PI);
+
+        let image = c.render(&w);
+
+        assert_ne!(image.get(5, 5), color::BLACK);
+        assert_eq!(image.get(0, 0), color::BLACK);
+        assert_eq!(c.pick(&w, 0, 0), None);
+    }
+
+    #[test]
+    fn shifting_the_lens_moves_the_canvas_without_turning_the_camera() {
+        let c = Camera::new(201, 101, PI / 2.0).with_lens_shift(0.5, -0.25);
+
+        let r = c.ray_for_pixel(100, 50);
+
+        assert_eq!(r.origin(), Point::new(0, 0, 0));
+        assert_eq!(r.direction(), Vector::new(0.5, -0.25, -1).norm());
+    }
+
+    #[test]
+    fn a_camera_is_a_pinhole_by_default() {
+        let c = Camera::new(201, 101, PI / 2.0);
+
+        assert_eq!(c.aperture, 0.0);
+        assert_eq!(c.samples, 1);
+
+        let mut rng = Rng::new(0);
+        for _ in 0..10 {
+            let r = c.sample_ray(100, 50, &mut rng).unwrap();
+            assert_eq!(r.origin(), Point::new(0, 0, 0));
+        }
+    }
+
+    #[test]
+    fn a_camera_takes_at_least_one_sample_per_pixel() {
+        let c = Camera::new(11, 11, PI / 2.0).with_samples(0);
+
+        assert_eq!(c.samples, 1);
+    }
+
+    #[test]
+    fn lens_rays_leave_from_within_the_aperture() {
+        let c = Camera::new(201, 101, PI / 2.0)
+            .with_aperture(0.25)
+            .with_focal_distance(5.0);
+        let mut rng = Rng::new(1);
+
+        for _ in 0..100 {
+            let r = c.sample_ray(100, 50, &mut rng).unwrap();
+            let o = r.origin();
+            assert!(o.x() * o.x() + o.y() * o.y() <= 0.25 * 0.25 + 1e-9);
+            assert!(feq(o.z(), 0.0));
+        }
+    }
+
+    #[test]
+    fn lens_rays_converge_on_the_focal_plane() {
+        let transform = Matrix::new().translate(0, -2, 5).rotate_y(PI / 4.0);
+        let c = Camera::new(201, 101, PI / 2.0)
+            .with_transform(transform)
+            .with_aperture(0.5)
+            .with_focal_distance(4.0);
+        let pinhole = Camera::new(201, 101, PI / 2.0).with_transform(transform);
+        let center = pinhole.ray_for_pixel(0, 0);
+        let mut rng = Rng::new(2);
+
+        // the focal plane is perpendicular to the view direction, so the
+        // center of pixel (0, 0) lies at a distance of 4 along the ray only
+        // after accounting for the ray's slant
+        let view = pinhole.ray_for_pixel(100, 50).direction();
+        let focus = center.position(4.0 / center.direction().dot(view));
+
+        for _ in 0..100 {
+            let r = c.ray_through(
+                0.5,
+                0.5,
+                sampling::concentric_disk(rng.next_f64(), rng.next_f64()),
+            );
+            let to_focus = focus - r.origin();
+            assert_eq!(r.direction(), to_focus.norm());
+        }
+    }
+
+    #[test]
+    fn sampled_rays_stay_within_their_pixel() {
+        let c = Camera::new(11, 11, PI / 2.0);
+        let corner = c.ray_through(2.0, 3.0, (0.0, 0.0)).direction();
+        let far_corner = c.ray_through(3.0, 4.0, (0.0, 0.0)).direction();
+        let mut rng = Rng::new(3);
+
+        for _ in 0..100 {
+            let d = c.sample_ray(2, 3, &mut rng).unwrap().direction();
+            let (x, y) = (d.x() / -d.z(), d.y() / -d.z());
+            assert!(x <= corner.x() / -corner.z() && x >= far_corner.x() / -far_corner.z());
+            assert!(y <= corner.y() / -corner.z() && y >= far_corner.y() / -far_corner.z());
+        }
+    }
+
+    #[test]
+    fn the_shutter_is_instantaneous_by_default() {
+        let c = Camera::new(11, 11, PI / 2.0);
+        let mut rng = Rng::new(4);
+
+        assert_eq!(c.ray_for_pixel(5, 5).time(), 0.0);
+        assert_eq!(c.sample_ray(5, 5, &mut rng).unwrap().time(), 0.0);
+    }
+
+    #[test]
+    fn sampled_rays_are_spread_over_the_shutter_interval() {
+        let c = Camera::new(11, 11, PI / 2.0).with_shutter(0.25, 0.75);
+        let mut rng = Rng::new(5);
+
+        let times: Vec<f64> = (0..100)
+            .map(|_| c.sample_ray(5, 5, &mut rng).unwrap().time())
+            .collect();
+
+        assert!(times.iter().all(|t| (0.25..0.75).contains(t)));
+        assert!(times.iter().any(|&t| t < 0.4));
+        assert!(times.iter().any(|&t| t > 0.6));
+        assert_eq!(c.ray_for_pixel(5, 5).time(), 0.25);
+    }
+
+    #[test]
+    fn a_moving_object_is_blurred() {
+        let light = PointLight::new(Point::new(-10, 10, -10), Color::new(1, 1, 1));
+        let sphere = Sphere::new().with_motion(
+            Matrix::new().translate(-1.5, 0, 0),
+            Matrix::new().translate(1.5, 0, 0),
+        );
+        let w = World::new()
+            .with_lights(vec![light])
+            .with_objects(vec![sphere]);
+        let from = Point::new(0, 0, -5);
+        let to = Point::new(0, 0, 0);
+        let up = Vector::new(0, 1, 0);
+        let c = Camera::new(11, 11, PI / 2.0)
+            .with_view_transform(from, to, up)
+            .with_shutter(0.0, 1.0)
+            .with_samples(32);
+
+        let image = c.render(&w);
+
+        // the sphere only covers the center of the image for part of the
+        // exposure, so it appears dimmer there than a stationary sphere would
+        let center = image.get(5, 5).red();
+        assert!(center > 0.05 && center < 0.7);
+    }
+
+    #[test]
+    fn rendering_with_several_samples_per_pixel() {
+        let w = default_world();
+        let from = Point::new(0, 0, -5);
+        let to = Point::new(0, 0, 0);
+        let up = Vector::new(0, 1, 0);
+        let c = Camera::new(11, 11, PI / 2.0)
+            .with_view_transform(from, to, up)
+            .with_samples(16);
+
+        let image = c.render(&w);
+        let again = c.render(&w);
+
+        // the center of the sphere is nearly flat, so averaging samples over
+        // the pixel only changes its color slightly; and the render is
+        // repeatable.
+        let center = image.get(5, 5);
+        assert!((center.red() - 0.38066).abs() < 0.01);
+        assert_eq!(center, again.get(5, 5));
+    }
+
+    #[test]
+    fn a_camera_uses_whitted_shading_by_default() {
+        let c = Camera::new(11, 11, PI / 2.0);
+
+        assert_eq!(c.integrator(), Integrator::Whitted);
+    }
+
+    #[test]
+    fn rendering_with_a_path_tracer_is_repeatable() {
+        let w = default_world();
+        let c = Camera::new(5, 5, PI / 2.0)
+            .with_view_transform(
+                Point::new(0, 0, -5),
+                Point::new(0, 0, 0),
+                Vector::new(0, 1, 0),
+            )
+            .with_integrator(Integrator::PathTracing { max_depth: 4 })
+            .with_samples(4);
+
+        let image = c.render(&w);
+        let again = c.render(&w);
+
+        assert_ne!(image.get(2, 2), color::BLACK);
+        assert_eq!(image.get(2, 2), again.get(2, 2));
+        assert_eq!(image.get(0, 0), color::BLACK);
+    }
+
+    #[test]
+    fn a_camera_takes_a_fixed_number_of_samples_by_default() {
+        let c = Camera::new(11, 11, PI / 2.0);
+
+        assert_eq!(c.adaptive_sampling(), None);
+    }
+
+    #[test]
+    fn adaptive_sampling_only_keeps_sampling_noisy_pixels() {
+        // a ball resting on a huge ball, lit only by a white sky
+        let material = Material::new().with_shading(Shading::Lambert);
+        let ball = Sphere::new().with_material(material.with_color(Color::new(0.9, 0.3, 0.2)));
+        let ground = Sphere::new()
+            .with_transform(Matrix::new().scale_u(100.0).translate(0, -101, 0))
+            .with_material(material);
+        let w = World::new()
+            .with_objects(vec![ball, ground])
+            .with_environment(Environment::Constant(color::WHITE));
+        let c = Camera::new(11, 11, PI / 3.0)
+            .with_view_transform(
+                Point::new(0, 1, -5),
+                Point::new(0, 0, 0),
+                Vector::new(0, 1, 0),
+            )
+            .with_integrator(Integrator::PathTracing { max_depth: 4 })
+            .with_samples(16)
+            .with_adaptive_sampling(AdaptiveSampling::new(0.02, 64));
+
+        let output = c.render_aovs(&w, &[Aov::SampleCount]);
+        let counts = output.sample_counts().unwrap();
+
+        // the sky is the same everywhere, so it only gets the minimum; the
+        // shadow under the ball is noisy, and gets the maximum
+        let total: f64 = (0..11)
+            .flat_map(|y| (0..11).map(move |x| (x, y)))
+            .map(|(x, y)| counts.get(x, y).red())
+            .sum();
+        assert_eq!(counts.get(5, 0).red(), 16.0);
+        assert_eq!(counts.get(5, 8).red(), 64.0);
+        assert!(total < (11 * 11 * 64 * 3 / 4) as f64);
+        assert_eq!(output.beauty().get(5, 0), color::WHITE);
+    }
+
+    #[test]
+    fn a_fixed_number_of_samples_is_the_minimum_for_adaptive_sampling() {
+        let w = World::new().with_environment(Environment::Constant(color::WHITE));
+        let c = Camera::new(3, 3, PI / 2.0)
+            .with_samples(8)
+            .with_adaptive_sampling(AdaptiveSampling::new(0.1, 4));
+
+        let output = c.render_aovs(&w, &[Aov::SampleCount]);
+
+        assert_eq!(output.sample_counts().unwrap().get(1, 1).red(), 8.0);
+    }
+
+    #[test]
+    fn adaptive_sampling_takes_a_few_samples_before_stopping() {
+        let w = World::new().with_environment(Environment::Constant(color::WHITE));
+        let c = Camera::new(3, 3, PI / 2.0).with_adaptive_sampling(AdaptiveSampling::new(0.1, 64));
+
+        let output = c.render_aovs(&w, &[Aov::SampleCount]);
+
+        assert_eq!(output.sample_counts().unwrap().get(1, 1).red(), 4.0);
+    }
+
+    #[test]
+    fn an_equirectangular_camera_sees_an_image_environment_as_it_is() {
+        let mut image = Canvas::new(8, 4);
+        for col in 0..8 {
+            for row in 0..4 {
+                image.set(col, row, Color::new(col as f64, row as f64, 0));
+            }
+        }
+        let w = World::new().with_environment(Environment::image(image));
+        let c = Camera::equirectangular(8, 4);
+
+        let rendered = c.render(&w);
+
+        for col in 0..8 {
+            for row in 0..4 {
+                assert_eq!(
+                    rendered.get(col, row),
+                    Color::new(col as f64, row as f64, 0)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn picking_the_object_at_a_pixel() {
+        let w = default_world();
+        let from = Point::new(0, 0, -5);
+        let to = Point::new(0, 0, 0);
+        let up = Vector::new(0, 1, 0);
+        let c = Camera::new(11, 11, PI / 2.0).with_view_transform(from, to, up);
+
+        let pick = c.pick(&w, 5, 5).unwrap();
+
+        assert_eq!(pick.object(), w.objects().next().unwrap().0);
+        assert_eq!(pick.point(), Point::new(0, 0, -1));
+        assert_eq!(pick.normal(), Vector::new(0, 0, -1));
+        assert!(feq(pick.distance(), 4.0));
+        assert_eq!(pick.material().color(), Color::new(0.8, 1.0, 0.6));
+    }
+
+    #[test]
+    fn picking_a_pixel_that_misses_everything() {
+        let w = default_world();
+        let from = Point::new(0, 0, -5);
+        let to = Point::new(0, 0, 0);
+        let up = Vector::new(0, 1, 0);
+        let c = Camera::new(11, 11, PI / 2.0).with_view_transform(from, to, up);
+
+        assert_eq!(c.pick(&w, 0, 0), None);
+    }
 }
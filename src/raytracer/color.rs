@@ -32,6 +32,24 @@ impl Color {
             blue: blue.into(),
         }
     }
+
+    pub fn red(&self) -> f64 {
+        self.red
+    }
+
+    pub fn green(&self) -> f64 {
+        self.green
+    }
+
+    pub fn blue(&self) -> f64 {
+        self.blue
+    }
+
+    /// The perceived brightness of the color, using the Rec. 709 weights for
+    /// linear RGB.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
 }
 
 impl From<Color> for Rgb<u8> {
@@ -44,6 +62,12 @@ impl From<Color> for Rgb<u8> {
     }
 }
 
+impl From<Color> for Rgb<f32> {
+    fn from(value: Color) -> Self {
+        Rgb([value.red as f32, value.green as f32, value.blue as f32])
+    }
+}
+
 impl Default for Color {
     fn default() -> Self {
         BLACK
@@ -138,6 +162,15 @@ mod tests {
         assert_eq!(c.blue, 1.7);
     }
 
+    #[test]
+    fn converting_a_color_to_floating_point_rgb_preserves_its_range() {
+        let c = Color::new(-0.5, 0.4, 1.75);
+
+        let rgb: Rgb<f32> = c.into();
+
+        assert_eq!(rgb, Rgb([-0.5, 0.4, 1.75]));
+    }
+
     #[test]
     fn adding_colors() {
         let c1 = Color::new(0.9, 0.6, 0.75);
@@ -0,0 +1,446 @@
+use crate::raytracer::{color, Canvas, Color, RenderOutput};
+
+/// The weights of the B3 spline, applied along each axis of the filter's
+/// 5x5 kernel.
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// The most passes the filter makes; by then its taps are 65536 pixels
+/// apart, which is wider than any image.
+const MAX_ITERATIONS: usize = 16;
+
+/// The smallest sigma allowed. At zero, not even a pixel's own color would
+/// count as similar to itself.
+const MIN_SIGMA: f64 = 1e-6;
+
+/// An edge-avoiding à-trous wavelet filter (Dammertz et al., 2010), for
+/// smoothing the noise out of renders taken with few samples per pixel.
+///
+/// Each pass blurs the image with a 5x5 kernel whose taps are spread twice
+/// as far apart as in the previous pass, so a few passes cover a wide area
+/// cheaply. Each tap is weighted by how similar it is to the pixel being
+/// filtered, in color and in whichever of the depth, normal and albedo
+/// buffers are available, so that the blur stops at the edges of objects,
+/// creases and textures.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Denoiser {
+    iterations: usize,
+    color_sigma: f64,
+    normal_sigma: f64,
+    depth_sigma: f64,
+    albedo_sigma: f64,
+}
+
+impl Denoiser {
+    pub fn new() -> Denoiser {
+        Denoiser {
+            iterations: 5,
+            color_sigma: 1.0,
+            normal_sigma: 0.3,
+            depth_sigma: 0.1,
+            albedo_sigma: 0.1,
+        }
+    }
+
+    /// Sets the number of passes, from one to sixteen. The last pass
+    /// reaches `2^iterations` pixels either side of each pixel.
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations.clamp(1, MAX_ITERATIONS);
+        self
+    }
+
+    /// Sets how different two colors can be before they stop being blended.
+    /// It's halved in each pass, as the image gets smoother. Like the other
+    /// sigmas, it's kept above zero.
+    pub fn with_color_sigma(mut self, color_sigma: f64) -> Self {
+        self.color_sigma = color_sigma.max(MIN_SIGMA);
+        self
+    }
+
+    pub fn with_normal_sigma(mut self, normal_sigma: f64) -> Self {
+        self.normal_sigma = normal_sigma.max(MIN_SIGMA);
+        self
+    }
+
+    /// Sets how different two depths can be, relative to the farther of the
+    /// two, before they stop being blended.
+    pub fn with_depth_sigma(mut self, depth_sigma: f64) -> Self {
+        self.depth_sigma = depth_sigma.max(MIN_SIGMA);
+        self
+    }
+
+    pub fn with_albedo_sigma(mut self, albedo_sigma: f64) -> Self {
+        self.albedo_sigma = albedo_sigma.max(MIN_SIGMA);
+        self
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    pub fn color_sigma(&self) -> f64 {
+        self.color_sigma
+    }
+
+    pub fn normal_sigma(&self) -> f64 {
+        self.normal_sigma
+    }
+
+    pub fn depth_sigma(&self) -> f64 {
+        self.depth_sigma
+    }
+
+    pub fn albedo_sigma(&self) -> f64 {
+        self.albedo_sigma
+    }
+
+    /// Returns a filtered copy of the image, guided by the depth, normal and
+    /// albedo buffers in `guides` (those that are missing are ignored). The
+    /// image is usually `guides.beauty()`, but may be any canvas of the same
+    /// size, such as one of the per-light buffers.
+    ///
+    /// Panics if a guide buffer isn't the same size as the image.
+    pub fn denoise(&self, image: &Canvas, guides: &RenderOutput) -> Canvas {
+        assert!(
+            [guides.depth(), guides.normal(), guides.albedo()]
+                .iter()
+                .flatten()
+                .all(|g| g.width() == image.width() && g.height() == image.height()),
+            "the guide buffers must be the same size as the image"
+        );
+
+        let mut filtered = self.pass(image, guides, 0);
+        for i in 1..self.iterations {
+            filtered = self.pass(&filtered, guides, i);
+        }
+        filtered
+    }
+
+    fn pass(&self, image: &Canvas, guides: &RenderOutput, iteration: usize) -> Canvas {
+        let (width, height) = (image.width() as isize, image.height() as isize);
+        let step = 1isize << iteration;
+        let color_sigma = self.color_sigma / 2f64.powi(iteration as i32);
+
+        let mut out = Canvas::new(image.width(), image.height());
+        for y in 0..height {
+            for x in 0..width {
+                let p = (x as usize, y as usize);
+                let mut sum = color::BLACK;
+                let mut total = 0.0;
+
+                for (j, ky) in KERNEL.iter().enumerate() {
+                    for (i, kx) in KERNEL.iter().enumerate() {
+                        let qx = x + (i as isize - 2) * step;
+                        let qy = y + (j as isize - 2) * step;
+                        if qx < 0 || qx >= width || qy < 0 || qy >= height {
+                            continue;
+                        }
+                        let q = (qx as usize, qy as usize);
+
+                        let color = image.get(q.0, q.1);
+                        let weight = kx
+                            * ky
+                            * self
+                                .similarity(image, guides, p, q, color_sigma)
+                                .map_or(0.0, |exponent| (-exponent).exp());
+                        sum = sum + color * weight;
+                        total += weight;
+                    }
+                }
+                // the center tap always has a weight of at least 9/64
+                out.set(p.0, p.1, sum / total);
+            }
+        }
+        out
+    }
+
+    // Returns the (negated) exponent of the weight given to pixel `q` when
+    // filtering pixel `p`, or `None` if `q` shouldn't contribute at all.
+    fn similarity(
+        &self,
+        image: &Canvas,
+        guides: &RenderOutput,
+        p: (usize, usize),
+        q: (usize, usize),
+        color_sigma: f64,
+    ) -> Option<f64> {
+        let mut exponent = distance_squared(image.get(p.0, p.1), image.get(q.0, q.1))
+            / (color_sigma * color_sigma);
+
+        if let Some(normal) = guides.normal() {
+            exponent += distance_squared(normal.get(p.0, p.1), normal.get(q.0, q.1))
+                / (self.normal_sigma * self.normal_sigma);
+        }
+        if let Some(albedo) = guides.albedo() {
+            exponent += distance_squared(albedo.get(p.0, p.1), albedo.get(q.0, q.1))
+                / (self.albedo_sigma * self.albedo_sigma);
+        }
+        if let Some(depth) = guides.depth() {
+            let difference = relative_difference(depth.get(p.0, p.1), depth.get(q.0, q.1))?;
+            exponent += (difference / self.depth_sigma).powi(2);
+        }
+        Some(exponent)
+    }
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn distance_squared(a: Color, b: Color) -> f64 {
+    let d = a - b;
+    d.red() * d.red() + d.green() * d.green() + d.blue() * d.blue()
+}
+
+// Compares two depths stored in a depth buffer. Pixels that see nothing are
+// infinitely far away, and are only similar to each other.
+fn relative_difference(a: Color, b: Color) -> Option<f64> {
+    let (a, b) = (a.red(), b.red());
+    if a == b {
+        Some(0.0)
+    } else if a.is_infinite() || b.is_infinite() {
+        None
+    } else {
+        Some((a - b).abs() / a.abs().max(b.abs()))
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::{view_transform, Matrix, Point, Vector},
+        raytracer::{Aov, Camera, Environment, Integrator, Material, Rng, Shading, World},
+        shape::Sphere,
+    };
+    use std::f64::consts::PI;
+
+    const SIZE: usize = 32;
+
+    fn filled(color: Color) -> Canvas {
+        let mut c = Canvas::new(SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                c.set(x, y, color);
+            }
+        }
+        c
+    }
+
+    // A gray image split down the middle, dark on the left and light on the
+    // right, with the same noise added to every channel.
+    fn noisy_edge(amplitude: f64) -> Canvas {
+        let mut rng = Rng::new(3);
+        let mut c = Canvas::new(SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let base = if x < SIZE / 2 { 0.2 } else { 0.8 };
+                let v = base + amplitude * (2.0 * rng.next_f64() - 1.0);
+                c.set(x, y, Color::new(v, v, v));
+            }
+        }
+        c
+    }
+
+    fn split(left: Color, right: Color) -> Canvas {
+        let mut c = Canvas::new(SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                c.set(x, y, if x < SIZE / 2 { left } else { right });
+            }
+        }
+        c
+    }
+
+    fn squared_error(image: &Canvas, expected: &Canvas) -> f64 {
+        let mut error = 0.0;
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                error += distance_squared(image.get(x, y), expected.get(x, y));
+            }
+        }
+        error
+    }
+
+    #[test]
+    fn the_default_denoiser() {
+        let d = Denoiser::new();
+
+        assert_eq!(d.iterations(), 5);
+        assert_eq!(d.color_sigma(), 1.0);
+        assert_eq!(d.normal_sigma(), 0.3);
+        assert_eq!(d.depth_sigma(), 0.1);
+        assert_eq!(d.albedo_sigma(), 0.1);
+    }
+
+    #[test]
+    #[should_panic(expected = "the guide buffers must be the same size as the image")]
+    fn guide_buffers_must_match_the_image() {
+        let guides = RenderOutput::new(Canvas::new(4, 4)).with_depth(Canvas::new(2, 2));
+
+        Denoiser::new().denoise(guides.beauty(), &guides);
+    }
+
+    #[test]
+    fn a_denoiser_makes_at_least_one_pass() {
+        let d = Denoiser::new().with_iterations(0);
+
+        assert_eq!(d.iterations(), 1);
+    }
+
+    #[test]
+    fn a_denoiser_makes_at_most_sixteen_passes() {
+        let d = Denoiser::new().with_iterations(64);
+        let image = noisy_edge(0.15);
+
+        let out = d.denoise(&image, &RenderOutput::new(noisy_edge(0.15)));
+
+        assert_eq!(d.iterations(), 16);
+        assert!(!out.get(0, 0).red().is_nan());
+    }
+
+    #[test]
+    fn sigmas_are_kept_above_zero() {
+        let d = Denoiser::new()
+            .with_color_sigma(0.0)
+            .with_normal_sigma(0.0)
+            .with_depth_sigma(-1.0)
+            .with_albedo_sigma(0.0);
+        let image = noisy_edge(0.15);
+        let guides = RenderOutput::new(noisy_edge(0.15))
+            .with_normal(filled(Color::new(0, 0, -1)))
+            .with_depth(filled(Color::new(4, 4, 4)))
+            .with_albedo(filled(color::WHITE));
+
+        let out = d.denoise(&image, &guides);
+
+        assert!(d.color_sigma() > 0.0);
+        assert!(d.depth_sigma() > 0.0);
+        // no two noisy pixels are close enough in color to be blended
+        assert_eq!(out.get(3, 3), image.get(3, 3));
+        assert_eq!(out.get(20, 11), image.get(20, 11));
+    }
+
+    #[test]
+    fn denoising_a_flat_image_leaves_it_unchanged() {
+        let gray = Color::new(0.3, 0.4, 0.5);
+        let image = filled(gray);
+        let guides = RenderOutput::new(filled(gray)).with_normal(filled(Color::new(0, 0, -1)));
+
+        let out = Denoiser::new().denoise(&image, &guides);
+
+        assert_eq!(out.get(0, 0), gray);
+        assert_eq!(out.get(17, 9), gray);
+        assert_eq!(out.get(SIZE - 1, SIZE - 1), gray);
+    }
+
+    #[test]
+    fn denoising_smooths_out_noise() {
+        let image = noisy_edge(0.15);
+        let guides = RenderOutput::new(noisy_edge(0.15));
+        let expected = noisy_edge(0.0);
+
+        let out = Denoiser::new().denoise(&image, &guides);
+
+        assert!(squared_error(&out, &expected) < squared_error(&image, &expected) / 4.0);
+    }
+
+    #[test]
+    fn normals_keep_edges_from_being_blurred() {
+        let image = noisy_edge(0.15);
+        let normals = split(Color::new(1, 0, 0), Color::new(0, 0, -1));
+        let blurry = Denoiser::new().with_color_sigma(10.0);
+
+        let unguided = blurry.denoise(&image, &RenderOutput::new(noisy_edge(0.15)));
+        let guided = blurry.denoise(
+            &image,
+            &RenderOutput::new(noisy_edge(0.15)).with_normal(normals),
+        );
+
+        let edge = SIZE / 2 - 1;
+        assert!(unguided.get(edge, 10).red() > 0.3);
+        assert!((guided.get(edge, 10).red() - 0.2).abs() < 0.05);
+        assert!((guided.get(edge + 1, 10).red() - 0.8).abs() < 0.05);
+    }
+
+    #[test]
+    fn albedo_keeps_textures_from_being_blurred() {
+        let image = noisy_edge(0.15);
+        let albedo = split(Color::new(0.2, 0.2, 0.2), Color::new(0.8, 0.8, 0.8));
+        let blurry = Denoiser::new().with_color_sigma(10.0);
+
+        let guided = blurry.denoise(
+            &image,
+            &RenderOutput::new(noisy_edge(0.15)).with_albedo(albedo),
+        );
+
+        let edge = SIZE / 2 - 1;
+        assert!((guided.get(edge, 10).red() - 0.2).abs() < 0.05);
+        assert!((guided.get(edge + 1, 10).red() - 0.8).abs() < 0.05);
+    }
+
+    #[test]
+    fn the_background_never_bleeds_into_objects() {
+        let image = noisy_edge(0.15);
+        let far = f64::INFINITY;
+        let depth = split(Color::new(5, 5, 5), Color::new(far, far, far));
+        let blurry = Denoiser::new().with_color_sigma(10.0);
+
+        let guided = blurry.denoise(
+            &image,
+            &RenderOutput::new(noisy_edge(0.15)).with_depth(depth),
+        );
+
+        let edge = SIZE / 2 - 1;
+        assert!((guided.get(edge, 10).red() - 0.2).abs() < 0.05);
+        assert!((guided.get(edge + 1, 10).red() - 0.8).abs() < 0.05);
+    }
+
+    #[test]
+    fn comparing_depths() {
+        let far = f64::INFINITY;
+        let inf = Color::new(far, far, far);
+
+        assert_eq!(relative_difference(inf, inf), Some(0.0));
+        assert_eq!(relative_difference(Color::new(4, 4, 4), inf), None);
+        assert_eq!(
+            relative_difference(Color::new(4, 4, 4), Color::new(5, 5, 5)),
+            Some(0.2)
+        );
+    }
+
+    #[test]
+    fn denoising_a_low_sample_render_brings_it_closer_to_the_reference() {
+        // a ball resting on a huge ball, lit only by a white sky
+        let material = Material::new().with_shading(Shading::Lambert);
+        let ball = Sphere::new().with_material(material.with_color(Color::new(0.9, 0.3, 0.2)));
+        let ground = Sphere::new()
+            .with_transform(Matrix::new().scale_u(100.0).translate(0, -101, 0))
+            .with_material(material.with_color(Color::new(0.8, 0.8, 0.8)));
+        let w = World::new()
+            .with_objects(vec![ball, ground])
+            .with_environment(Environment::Constant(color::WHITE));
+        let transform = view_transform(
+            Point::new(0, 1, -5),
+            Point::new(0, 0, 0),
+            Vector::new(0, 1, 0),
+        );
+        let camera = |samples| {
+            Camera::new(24, 24, PI / 3.0)
+                .with_transform(transform)
+                .with_samples(samples)
+                .with_integrator(Integrator::PathTracing { max_depth: 4 })
+        };
+
+        let reference = camera(256).render(&w);
+        let noisy = camera(4).render_aovs(&w, &[Aov::Depth, Aov::Normal, Aov::Albedo]);
+        let denoised = Denoiser::new().denoise(noisy.beauty(), &noisy);
+
+        assert!(
+            squared_error(&denoised, &reference) < squared_error(noisy.beauty(), &reference) / 2.0
+        );
+    }
+}
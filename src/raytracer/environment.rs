@@ -0,0 +1,173 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::sync::Arc;
+
+use image::ImageResult;
+
+use crate::geometry::{Tuple, Vector};
+use crate::raytracer::{color, Canvas, Color, Sky};
+
+/// The light arriving from infinitely far away, in every direction: seen by
+/// rays that miss every object, and lighting the scene under sampling
+/// integrators.
+#[derive(Clone)]
+pub enum Environment {
+    /// The same color in every direction. A black environment is the
+    /// default.
+    Constant(Color),
+    /// A sky that blends from `bottom` straight down, to `top` straight up.
+    Gradient { bottom: Color, top: Color },
+    /// An equirectangular (latitude-longitude) image, such as an HDR light
+    /// probe. The center of the image is towards -z, the top is straight up,
+    /// and longitude decreases from left to right, as rendered by
+    /// `Camera::equirectangular`. An empty image is black.
+    Image(Arc<Canvas>),
+    /// A procedural daylight sky. Add its `sun_light` to the world's lights
+    /// to light the scene with the matching sun.
+    Sky(Sky),
+}
+
+impl Environment {
+    /// Panics if the image is empty.
+    pub fn image(image: Canvas) -> Environment {
+        assert!(
+            image.width() > 0 && image.height() > 0,
+            "an environment image needs at least one pixel"
+        );
+        Environment::Image(Arc::new(image))
+    }
+
+    /// Loads an equirectangular environment from an HDR or EXR file.
+    pub fn load(path: &str) -> ImageResult<Environment> {
+        Ok(Environment::image(Canvas::load_hdr(path)?))
+    }
+
+    /// Returns the light arriving from the given direction.
+    pub fn radiance(&self, direction: Vector) -> Color {
+        match self {
+            Environment::Constant(color) => *color,
+            Environment::Gradient { bottom, top } => {
+                let t = 0.5 * (direction.norm().y() + 1.0);
+                *bottom * (1.0 - t) + *top * t
+            }
+            Environment::Image(image) if image.width() == 0 || image.height() == 0 => color::BLACK,
+            Environment::Image(image) => {
+                let d = direction.norm();
+                let latitude = d.y().clamp(-1.0, 1.0).asin();
+                let longitude = d.x().atan2(-d.z());
+
+                let u = (PI - longitude) / (2.0 * PI);
+                let v = (PI / 2.0 - latitude) / PI;
+                let col = ((u * image.width() as f64) as usize).min(image.width() - 1);
+                let row = ((v * image.height() as f64) as usize).min(image.height() - 1);
+                image.get(col, row)
+            }
+            Environment::Sky(sky) => sky.radiance(direction),
+        }
+    }
+
+    // A black environment gives off no light, so there's no point sampling
+    // it.
+    pub(crate) fn is_black(&self) -> bool {
+        matches!(self, Environment::Constant(c) if *c == color::BLACK)
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::Constant(color::BLACK)
+    }
+}
+
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Environment::Constant(color) => f.debug_tuple("Constant").field(color).finish(),
+            Environment::Gradient { bottom, top } => f
+                .debug_struct("Gradient")
+                .field("bottom", bottom)
+                .field("top", top)
+                .finish(),
+            Environment::Image(image) => f
+                .debug_tuple("Image")
+                .field(&(image.width(), image.height()))
+                .finish(),
+            Environment::Sky(sky) => f.debug_tuple("Sky").field(sky).finish(),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_default_environment_is_black() {
+        let e = Environment::default();
+
+        assert_eq!(e.radiance(Vector::new(1, 2, 3)), color::BLACK);
+    }
+
+    #[test]
+    fn a_constant_environment() {
+        let e = Environment::Constant(Color::new(0.2, 0.4, 0.6));
+
+        assert_eq!(e.radiance(Vector::new(0, -1, 0)), Color::new(0.2, 0.4, 0.6));
+        assert_eq!(e.radiance(Vector::new(1, 2, 3)), Color::new(0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn a_gradient_environment_blends_from_bottom_to_top() {
+        let e = Environment::Gradient {
+            bottom: Color::new(1, 1, 1),
+            top: Color::new(0, 0.5, 1),
+        };
+
+        assert_eq!(e.radiance(Vector::new(0, -1, 0)), Color::new(1, 1, 1));
+        assert_eq!(e.radiance(Vector::new(0, 3, 0)), Color::new(0, 0.5, 1));
+        assert_eq!(e.radiance(Vector::new(1, 0, 0)), Color::new(0.5, 0.75, 1));
+    }
+
+    #[test]
+    fn a_sky_environment() {
+        let sky = Sky::new(Vector::new(0, 1, -1), 3.0);
+        let e = Environment::Sky(sky);
+
+        assert_eq!(
+            e.radiance(Vector::new(0, 1, 0)),
+            sky.radiance(Vector::new(0, 1, 0))
+        );
+    }
+
+    #[test]
+    fn an_image_environment_is_equirectangular() {
+        let mut image = Canvas::new(4, 2);
+        image.set(0, 0, Color::new(1, 0, 0));
+        image.set(1, 0, Color::new(0, 1, 0));
+        image.set(2, 1, Color::new(0, 0, 1));
+        image.set(3, 1, Color::new(1, 1, 0));
+        let e = Environment::image(image);
+
+        // the left of the image looks towards +x, then -z in the center
+        // and -x on the right, with +z at the edges
+        assert_eq!(e.radiance(Vector::new(1, 0.1, 0.1)), Color::new(1, 0, 0));
+        assert_eq!(e.radiance(Vector::new(1, 0.1, -1)), Color::new(0, 1, 0));
+        assert_eq!(e.radiance(Vector::new(-0.1, -0.1, -1)), Color::new(0, 0, 1));
+        assert_eq!(e.radiance(Vector::new(-1, -0.1, 0.1)), Color::new(1, 1, 0));
+    }
+
+    #[test]
+    fn an_empty_image_environment_is_black() {
+        let e = Environment::Image(Arc::new(Canvas::new(0, 0)));
+
+        assert_eq!(e.radiance(Vector::new(1, 2, 3)), color::BLACK);
+    }
+
+    #[test]
+    #[should_panic(expected = "an environment image needs at least one pixel")]
+    fn an_image_environment_cannot_be_empty() {
+        Environment::image(Canvas::new(0, 0));
+    }
+}
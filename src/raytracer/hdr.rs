@@ -0,0 +1,178 @@
+extern crate image;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use crate::raytracer::{Canvas, Color};
+use image::codecs::hdr::{HdrDecoder, HdrEncoder};
+use image::error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use image::{ImageBuffer, ImageError, ImageResult, Rgb, Rgb32FImage};
+
+impl Canvas {
+    /// Saves the canvas as floating point data, without clamping or
+    /// quantizing its colors. The format is chosen by the path's extension:
+    /// Radiance RGBE (".hdr"), portable float map (".pfm") or OpenEXR (".exr").
+    pub fn save_hdr(&self, path: &str) -> ImageResult<()> {
+        match extension(path).as_deref() {
+            Some("hdr") => {
+                let writer = BufWriter::new(File::create(path)?);
+                HdrEncoder::new(writer).encode(&self.to_rgb_f32(), self.width(), self.height())
+            }
+            Some("pfm") => Ok(std::fs::write(path, self.to_pfm())?),
+            Some("exr") => self.to_rgb32f_image().save(path),
+            _ => Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormatHint::PathExtension(path.into()),
+                    UnsupportedErrorKind::Format(ImageFormatHint::PathExtension(path.into())),
+                ),
+            )),
+        }
+    }
+
+    /// Loads a floating point image, such as a Radiance RGBE (".hdr") or
+    /// OpenEXR (".exr") file, without clamping its colors.
+    pub fn load_hdr(path: &str) -> ImageResult<Canvas> {
+        // the generic decoder converts Radiance files to 8 bits per channel
+        let image = match extension(path).as_deref() {
+            Some("hdr") => {
+                let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+                let (width, height) = (decoder.metadata().width, decoder.metadata().height);
+                let pixels = decoder.read_image_hdr()?;
+                let samples = pixels.into_iter().flat_map(|p| p.0).collect();
+                Rgb32FImage::from_raw(width, height, samples)
+                    .expect("decoded image has the wrong size")
+            }
+            _ => image::open(path)?.into_rgb32f(),
+        };
+
+        let mut canvas = Canvas::new(image.width() as usize, image.height() as usize);
+        for (col, row, pixel) in image.enumerate_pixels() {
+            let [r, g, b] = pixel.0;
+            canvas.set(col as usize, row as usize, Color::new(r, g, b));
+        }
+        Ok(canvas)
+    }
+
+    /// Encodes the canvas as a little-endian portable float map. Note that
+    /// PFM stores its rows from bottom to top.
+    pub fn to_pfm(&self) -> Vec<u8> {
+        let mut out = format!("PF\n{} {}\n-1.0\n", self.width(), self.height()).into_bytes();
+        for row in (0..self.height()).rev() {
+            for col in 0..self.width() {
+                let Rgb::<f32>(rgb) = self.get(col, row).into();
+                for sample in rgb {
+                    out.extend_from_slice(&sample.to_le_bytes());
+                }
+            }
+        }
+        out
+    }
+
+    fn to_rgb_f32(&self) -> Vec<Rgb<f32>> {
+        (0..self.height())
+            .flat_map(|row| (0..self.width()).map(move |col| self.get(col, row).into()))
+            .collect()
+    }
+
+    fn to_rgb32f_image(&self) -> Rgb32FImage {
+        let mut image: Rgb32FImage = ImageBuffer::new(self.width() as u32, self.height() as u32);
+        for (col, row, pixel) in image.enumerate_pixels_mut() {
+            *pixel = self.get(col as usize, row as usize).into()
+        }
+        image
+    }
+}
+
+fn extension(path: &str) -> Option<String> {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_canvas() -> Canvas {
+        let mut c = Canvas::new(3, 2);
+        c.set(0, 0, Color::new(4.0, 0.5, 0.0));
+        c.set(2, 1, Color::new(0.125, 16.0, 1.0));
+        c
+    }
+
+    fn temp_path(name: &str) -> String {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("ray-tracer-{}-{}", std::process::id(), name));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn constructing_a_pfm() {
+        let mut c = Canvas::new(1, 2);
+        c.set(0, 0, Color::new(2.5, 0, 0));
+        c.set(0, 1, Color::new(0, 0, -1));
+
+        let pfm = c.to_pfm();
+
+        let mut expected = b"PF\n1 2\n-1.0\n".to_vec();
+        for sample in [0.0f32, 0.0, -1.0, 2.5, 0.0, 0.0] {
+            expected.extend_from_slice(&sample.to_le_bytes());
+        }
+        assert_eq!(pfm, expected);
+    }
+
+    #[test]
+    fn saving_an_hdr_file_keeps_values_above_one() {
+        let c = sample_canvas();
+        let path = temp_path("canvas.hdr");
+
+        c.save_hdr(&path).unwrap();
+        let decoder = HdrDecoder::new(BufReader::new(File::open(&path).unwrap())).unwrap();
+        let pixels = decoder.read_image_hdr().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(pixels.len(), 6);
+        assert_eq!(pixels[0], Rgb([4.0, 0.5, 0.0]));
+        assert_eq!(pixels[5], Rgb([0.125, 16.0, 1.0]));
+    }
+
+    #[test]
+    fn saving_an_exr_file_keeps_values_above_one() {
+        let c = sample_canvas();
+        let path = temp_path("canvas.exr");
+
+        c.save_hdr(&path).unwrap();
+        let image = image::open(&path).unwrap().into_rgb32f();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(image.dimensions(), (3, 2));
+        assert_eq!(*image.get_pixel(0, 0), Rgb([4.0, 0.5, 0.0]));
+        assert_eq!(*image.get_pixel(2, 1), Rgb([0.125, 16.0, 1.0]));
+    }
+
+    #[test]
+    fn loading_a_saved_hdr_file() {
+        let c = sample_canvas();
+        let path = temp_path("reload.hdr");
+
+        c.save_hdr(&path).unwrap();
+        let loaded = Canvas::load_hdr(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.width(), 3);
+        assert_eq!(loaded.height(), 2);
+        assert_eq!(loaded.get(0, 0), Color::new(4.0, 0.5, 0.0));
+        assert_eq!(loaded.get(2, 1), Color::new(0.125, 16.0, 1.0));
+    }
+
+    #[test]
+    fn saving_with_an_unknown_extension_fails() {
+        let c = sample_canvas();
+
+        assert!(c.save_hdr(&temp_path("canvas.png")).is_err());
+    }
+}
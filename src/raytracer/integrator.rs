@@ -0,0 +1,554 @@
+use std::f64::consts::PI;
+
+use crate::raytracer::{
+    color, sampling, AmbientOcclusion, Color, IntersectionState, Ray, Rng, World,
+};
+use crate::EPSILON;
+
+/// The number of bounces after which paths may be terminated by Russian
+/// roulette.
+const MIN_DEPTH: usize = 3;
+
+/// The algorithm used to compute the color seen along a camera ray.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Integrator {
+    /// Phong shading with hard shadows from point lights, as computed by
+    /// `World::color_at`. This is deterministic, so a single sample per
+    /// pixel suffices.
+    Whitted,
+    /// Monte Carlo path tracing with interreflection, for global
+    /// illumination and color bleeding. Each path samples the point lights,
+    /// emissive objects and environment directly at every bounce, and is
+    /// terminated by Russian roulette (or at `max_depth` bounces).
+    /// The result is noisy, so use many samples per pixel.
+    PathTracing { max_depth: usize },
+    /// Renders each surface white, darkened by ambient occlusion alone, for
+    /// "clay" renders that show off the shapes in a scene. Lights and
+    /// materials are ignored.
+    AmbientOcclusion(AmbientOcclusion),
+}
+
+impl Integrator {
+    /// Returns true if the integrator uses random sampling, and so needs
+    /// more than one sample per pixel to converge.
+    pub fn is_stochastic(&self) -> bool {
+        match self {
+            Integrator::Whitted => false,
+            Integrator::PathTracing { .. } => true,
+            Integrator::AmbientOcclusion(_) => true,
+        }
+    }
+
+    pub fn color_at(&self, world: &World, ray: Ray, rng: &mut Rng) -> Color {
+        match *self {
+            Integrator::Whitted => world.color_at(ray),
+            Integrator::PathTracing { max_depth } => path_trace(world, ray, rng, max_depth),
+            Integrator::AmbientOcclusion(ao) => ambient_occlusion(world, ray, rng, ao),
+        }
+    }
+}
+
+// Surfaces reflect light as given by their material's BRDF, ignoring the
+// ambient term; for Phong materials, that's just the diffuse part. Point
+// lights contribute just as they do to Whitted shading, without falloff, so
+// that scenes look alike under either integrator. Emissive objects are
+// sampled as area lights, so their emission is only counted when seen
+// directly by the camera; counting it on later bounces as well would count
+// it twice. The environment is sampled directly too, so it's likewise only
+// seen by rays straight from the camera, or through volumes in front of it.
+fn path_trace(world: &World, ray: Ray, rng: &mut Rng, max_depth: usize) -> Color {
+    let mut radiance = color::BLACK;
+    let mut throughput = color::WHITE;
+    let mut ray = ray;
+    // until the path bounces off a surface, it sees what the camera sees
+    let mut from_camera = true;
+
+    'path: for depth in 0..max_depth {
+        // crossing a volume doesn't use up a bounce
+        let state = loop {
+            let xs = world.intersect(ray);
+            let hit = match xs.hit() {
+                Some(hit) => hit,
+                None => {
+                    if from_camera {
+                        radiance =
+                            radiance + throughput * world.environment().radiance(ray.direction());
+                    }
+                    break 'path;
+                }
+            };
+
+            if let Some(fog) = world.fog() {
+                let transmittance = fog.transmittance(world.distance_to(ray, hit.t()));
+                radiance = radiance + throughput * fog.color() * (1.0 - transmittance);
+                throughput = throughput * transmittance;
+            }
+
+            match hit.object().material().medium() {
+                Some(medium) => {
+                    let segment = world.volume_segment(ray, hit, medium);
+                    radiance = radiance + throughput * segment.scattered;
+                    throughput = throughput * segment.transmittance;
+                    ray = segment.rest;
+                }
+                None => break hit.compute_state(ray),
+            }
+        };
+        let material = state.object().material();
+
+        if from_camera {
+            radiance = radiance + throughput * material.emission();
+        }
+
+        // next-event estimation: sample every light directly
+        for &light in world.point_lights() {
+            let light_v = (light.position() - state.point()).norm();
+            let light_dot_normal = light_v.dot(state.normal_v());
+            if light_dot_normal > 0.0 && !world.is_shadowed(state.over_point(), light, state.time())
+            {
+                let f = material.brdf(state.normal_v(), state.eye_v(), light_v);
+                let transmittance =
+                    world.light_transmittance(state.over_point(), light.position(), state.time());
+                radiance = radiance
+                    + throughput * f * light.intensity() * transmittance * (PI * light_dot_normal);
+            }
+        }
+        radiance = radiance + throughput * sample_emitters(world, &state, rng);
+        radiance = radiance + throughput * sample_environment(world, &state, rng);
+
+        let (direction, weight) = match material.sample_brdf(state.normal_v(), state.eye_v(), rng) {
+            Some(sample) => sample,
+            None => break,
+        };
+        throughput = throughput * weight;
+
+        // Russian roulette: terminate dim paths at random, and compensate the
+        // survivors, so the estimate stays unbiased
+        if depth >= MIN_DEPTH {
+            let survival = throughput
+                .red()
+                .max(throughput.green())
+                .max(throughput.blue())
+                .min(0.95);
+            if rng.next_f64() >= survival {
+                break;
+            }
+            throughput = throughput / survival;
+        }
+
+        from_camera = false;
+        ray = Ray::new(state.over_point(), direction)
+            .with_interval(0.0, f64::INFINITY)
+            .with_time(ray.time());
+    }
+
+    radiance
+}
+
+fn ambient_occlusion(world: &World, ray: Ray, rng: &mut Rng, ao: AmbientOcclusion) -> Color {
+    let xs = world.intersect(ray);
+    match xs.hit() {
+        Some(hit) => {
+            let state = hit.compute_state(ray);
+            let open = ao.unoccluded(
+                world,
+                state.over_point(),
+                state.normal_v(),
+                state.time(),
+                rng,
+            );
+            color::WHITE * open
+        }
+        None => color::BLACK,
+    }
+}
+
+// Estimates the light reflected towards the eye from the emissive objects in
+// the world, by picking a random point on each of them. Emitters glow on both
+// sides, so a point inside an emissive sphere is lit by it too.
+fn sample_emitters(world: &World, state: &IntersectionState, rng: &mut Rng) -> Color {
+    let material = state.object().material();
+    let mut total = color::BLACK;
+
+    for emitter in world.emitters() {
+        let (point, normal, pdf) =
+            emitter.sample_surface(rng.next_f64(), rng.next_f64(), state.time());
+        let v = point - state.over_point();
+        let distance = v.mag();
+        let direction = v / distance;
+
+        let cos_surface = direction.dot(state.normal_v());
+        let cos_emitter = normal.dot(direction).abs();
+        if cos_surface <= 0.0 || cos_emitter == 0.0 {
+            continue;
+        }
+
+        let shadow_ray = Ray::new(state.over_point(), direction)
+            .with_interval(0.0, distance - EPSILON)
+            .with_time(state.time());
+        if world.is_occluded(shadow_ray) {
+            continue;
+        }
+
+        let f = material.brdf(state.normal_v(), state.eye_v(), direction);
+        let emission = emitter.material().emission()
+            * world.light_transmittance(state.over_point(), point, state.time());
+        total = total + f * emission * (cos_surface * cos_emitter / (distance * distance * pdf));
+    }
+
+    total
+}
+
+// Estimates the light reflected towards the eye from the environment, by
+// following a cosine-weighted direction to the sky. For a Lambertian surface,
+// this is just the sky's radiance times the surface's albedo.
+fn sample_environment(world: &World, state: &IntersectionState, rng: &mut Rng) -> Color {
+    let environment = world.environment();
+    if environment.is_black() {
+        return color::BLACK;
+    }
+
+    let direction = sampling::cosine_hemisphere(state.normal_v(), rng);
+    let shadow_ray = Ray::new(state.over_point(), direction)
+        .with_interval(0.0, f64::INFINITY)
+        .with_time(state.time());
+    if world.is_occluded(shadow_ray) {
+        return color::BLACK;
+    }
+
+    let f = state
+        .object()
+        .material()
+        .brdf(state.normal_v(), state.eye_v(), direction);
+    let transmittance =
+        world.environment_transmittance(state.over_point(), direction, state.time());
+    f * environment.radiance(direction) * transmittance * PI
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::{Matrix, Point, Vector},
+        raytracer::{
+            world::test_utils::default_world, Environment, Material, Medium, PointLight, Shading,
+        },
+        shape::Sphere,
+    };
+
+    #[test]
+    fn the_whitted_integrator_is_the_world_color() {
+        let w = default_world();
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+        let mut rng = Rng::new(0);
+
+        let c = Integrator::Whitted.color_at(&w, r, &mut rng);
+
+        assert_eq!(c, w.color_at(r));
+        assert!(!Integrator::Whitted.is_stochastic());
+    }
+
+    #[test]
+    fn a_path_that_misses_everything_is_black() {
+        let w = default_world();
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 1, 0));
+        let mut rng = Rng::new(0);
+        let pt = Integrator::PathTracing { max_depth: 8 };
+
+        assert!(pt.is_stochastic());
+        assert_eq!(pt.color_at(&w, r, &mut rng), color::BLACK);
+    }
+
+    #[test]
+    fn direct_lighting_matches_the_phong_diffuse_term() {
+        let material = Material::new()
+            .with_color(Color::new(0.8, 1.0, 0.6))
+            .with_diffuse(0.7);
+        let w = World::new()
+            .with_lights(vec![PointLight::new(Point::new(0, 0, -10), color::WHITE)])
+            .with_objects(vec![Sphere::new().with_material(material)]);
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+        let mut rng = Rng::new(0);
+
+        // with a single bounce, there's no indirect light
+        let c = Integrator::PathTracing { max_depth: 1 }.color_at(&w, r, &mut rng);
+
+        assert_eq!(c, Color::new(0.56, 0.7, 0.42));
+    }
+
+    #[test]
+    fn an_emissive_surface_is_visible_without_lights() {
+        let material = Material::new()
+            .with_diffuse(0.0)
+            .with_emission(Color::new(1, 0.5, 0.25));
+        let w = World::new().with_objects(vec![Sphere::new().with_material(material)]);
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+        let mut rng = Rng::new(0);
+
+        let c = Integrator::PathTracing { max_depth: 8 }.color_at(&w, r, &mut rng);
+
+        assert_eq!(c, Color::new(1, 0.5, 0.25));
+    }
+
+    #[test]
+    fn path_tracing_converges_inside_a_furnace() {
+        // inside a closed sphere that emits 1 and reflects half of what falls
+        // on it, every point sees 1 + 0.5 + 0.25 + ... = 2
+        let material = Material::new()
+            .with_color(color::WHITE)
+            .with_diffuse(0.5)
+            .with_emission(color::WHITE);
+        let w = World::new().with_objects(vec![Sphere::new()
+            .with_transform(Matrix::new().scale_u(10))
+            .with_material(material)]);
+        let pt = Integrator::PathTracing { max_depth: 100 };
+        let mut rng = Rng::new(1);
+        let samples = 4000;
+
+        let total = (0..samples)
+            .map(|i| {
+                let direction = sampling::cosine_hemisphere(Vector::new(0, 0, 1), &mut rng);
+                let direction = if i % 2 == 0 { direction } else { -direction };
+                pt.color_at(&w, Ray::new(Point::new(0, 0, 0), direction), &mut rng)
+            })
+            .fold(color::BLACK, |acc, c| acc + c);
+        let mean = total / samples;
+
+        assert!((mean.red() - 2.0).abs() < 0.05, "mean was {:?}", mean);
+    }
+
+    #[test]
+    fn an_emissive_sphere_lights_the_surface_below_it() {
+        // a sphere of radius r and radiance L, at a distance d straight above
+        // a diffuse surface, reflects albedo * L * r^2 / d^2
+        let floor = Sphere::new().with_transform(Matrix::new().scale(100, 0.01, 100));
+        let lamp = Sphere::new()
+            .with_transform(Matrix::new().translate(0, 5.01, 0))
+            .with_material(Material::new().with_emission(Color::new(10, 10, 10)));
+        let w = World::new().with_objects(vec![floor, lamp]);
+        let r = Ray::new(Point::new(0, 1.01, -1), Vector::new(0, -1, 1).norm());
+        let pt = Integrator::PathTracing { max_depth: 1 };
+        let mut rng = Rng::new(3);
+        let samples = 20000;
+
+        let total = (0..samples)
+            .map(|_| pt.color_at(&w, r, &mut rng))
+            .fold(color::BLACK, |acc, c| acc + c);
+        let mean = total / samples;
+
+        assert!(
+            (mean.red() - 0.9 * 10.0 / 25.0).abs() < 0.01,
+            "mean was {:?}",
+            mean
+        );
+    }
+
+    #[test]
+    fn emitters_do_not_light_surfaces_through_other_objects() {
+        let floor = Sphere::new().with_transform(Matrix::new().scale(100, 0.01, 100));
+        let blocker =
+            Sphere::new().with_transform(Matrix::new().scale(3, 0.1, 3).translate(0, 2, 0));
+        let lamp = Sphere::new()
+            .with_transform(Matrix::new().translate(0, 5, 0))
+            .with_material(Material::new().with_emission(Color::new(10, 10, 10)));
+        let w = World::new().with_objects(vec![floor, blocker, lamp]);
+        let r = Ray::new(Point::new(0, 1.01, -1), Vector::new(0, -1, 1).norm());
+        let mut rng = Rng::new(4);
+
+        for _ in 0..100 {
+            let c = Integrator::PathTracing { max_depth: 1 }.color_at(&w, r, &mut rng);
+            assert_eq!(c, color::BLACK);
+        }
+    }
+
+    #[test]
+    fn ambient_occlusion_renders_ignore_lights_and_materials() {
+        let material = Material::new().with_color(Color::new(1, 0, 0));
+        let w = World::new().with_objects(vec![Sphere::new().with_material(material)]);
+        let ao = Integrator::AmbientOcclusion(AmbientOcclusion::new(8, 1.0));
+        let mut rng = Rng::new(0);
+
+        let hit = ao.color_at(
+            &w,
+            Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1)),
+            &mut rng,
+        );
+        let miss = ao.color_at(
+            &w,
+            Ray::new(Point::new(0, 0, -5), Vector::new(0, 1, 0)),
+            &mut rng,
+        );
+
+        assert!(ao.is_stochastic());
+        assert_eq!(hit, color::WHITE);
+        assert_eq!(miss, color::BLACK);
+    }
+
+    #[test]
+    fn the_environment_lights_the_scene() {
+        // a white Lambertian sphere under a uniform sky reflects the sky
+        // color wherever it isn't shadowed by itself
+        let material = Material::new()
+            .with_diffuse(1.0)
+            .with_shading(Shading::Lambert);
+        let w = World::new()
+            .with_objects(vec![Sphere::new().with_material(material)])
+            .with_environment(Environment::Constant(Color::new(0.5, 0.5, 0.5)));
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+        let mut rng = Rng::new(6);
+
+        let c = Integrator::PathTracing { max_depth: 4 }.color_at(&w, r, &mut rng);
+        let miss = Integrator::PathTracing { max_depth: 4 }.color_at(
+            &w,
+            Ray::new(Point::new(0, 0, -5), Vector::new(0, 1, 0)),
+            &mut rng,
+        );
+
+        assert_eq!(c, Color::new(0.5, 0.5, 0.5));
+        assert_eq!(miss, Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn the_environment_is_sampled_at_every_bounce() {
+        // a diffuse floor under a sky that blends from grey at the horizon to
+        // white overhead sees 5/6 of white on average, weighted by the cosine
+        // term
+        let floor = Sphere::new().with_transform(Matrix::new().scale(100, 0.01, 100));
+        let w = World::new()
+            .with_objects(vec![floor])
+            .with_environment(Environment::Gradient {
+                bottom: color::BLACK,
+                top: color::WHITE,
+            });
+        let r = Ray::new(Point::new(0, 1.01, -1), Vector::new(0, -1, 1).norm());
+        let pt = Integrator::PathTracing { max_depth: 1 };
+        let mut rng = Rng::new(9);
+        let samples = 20000;
+
+        let total = (0..samples)
+            .map(|_| pt.color_at(&w, r, &mut rng))
+            .fold(color::BLACK, |acc, c| acc + c);
+        let mean = total / samples;
+
+        assert!(
+            (mean.red() - 0.9 * 5.0 / 6.0).abs() < 0.01,
+            "mean was {:?}",
+            mean
+        );
+    }
+
+    #[test]
+    fn objects_shadow_surfaces_from_the_environment() {
+        let floor = Sphere::new().with_transform(Matrix::new().scale(100, 0.01, 100));
+        let roof =
+            Sphere::new().with_transform(Matrix::new().scale(100, 0.1, 100).translate(0, 2, 0));
+        let w = World::new()
+            .with_objects(vec![floor, roof])
+            .with_environment(Environment::Constant(color::WHITE));
+        let r = Ray::new(Point::new(0, 1.01, -1), Vector::new(0, -1, 1).norm());
+        let mut rng = Rng::new(10);
+
+        for _ in 0..100 {
+            let c = Integrator::PathTracing { max_depth: 1 }.color_at(&w, r, &mut rng);
+            assert_eq!(c, color::BLACK);
+        }
+    }
+
+    #[test]
+    fn paths_pass_through_volumes() {
+        let medium = Medium::new(Color::new(0.5, 0.5, 0.5), color::BLACK);
+        let w = World::new()
+            .with_objects(vec![
+                Sphere::new().with_material(Material::new().with_medium(medium))
+            ])
+            .with_environment(Environment::Constant(color::WHITE));
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+        let mut rng = Rng::new(8);
+
+        let c = Integrator::PathTracing { max_depth: 4 }.color_at(&w, r, &mut rng);
+
+        assert_eq!(c, medium.transmittance(2.0));
+        assert_eq!(c, w.color_at(r));
+    }
+
+    #[test]
+    fn crossing_a_volume_does_not_use_up_a_bounce() {
+        let lamp = Sphere::new()
+            .with_transform(Matrix::new().translate(0, 0, 3))
+            .with_material(
+                Material::new()
+                    .with_diffuse(0.0)
+                    .with_emission(color::WHITE),
+            );
+        let w = World::new().with_objects(vec![
+            Sphere::new().with_material(Material::new().with_medium(Medium::default())),
+            lamp,
+        ]);
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+        let mut rng = Rng::new(11);
+
+        let c = Integrator::PathTracing { max_depth: 4 }.color_at(&w, r, &mut rng);
+
+        assert_eq!(c, color::WHITE);
+    }
+
+    #[test]
+    fn the_environment_is_seen_through_an_empty_volume() {
+        let w = World::new()
+            .with_objects(vec![
+                Sphere::new().with_material(Material::new().with_medium(Medium::default()))
+            ])
+            .with_environment(Environment::Constant(color::WHITE));
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+        let mut rng = Rng::new(12);
+
+        let c = Integrator::PathTracing { max_depth: 1 }.color_at(&w, r, &mut rng);
+
+        assert_eq!(c, color::WHITE);
+    }
+
+    #[test]
+    fn volumes_dim_the_light_falling_on_surfaces_behind_them() {
+        let medium = Medium::new(Color::new(0.5, 0.5, 0.5), color::BLACK);
+        let floor = Sphere::new()
+            .with_transform(Matrix::new().scale(100, 0.01, 100))
+            .with_material(Material::new().with_ambient(0.0).with_specular(0.0));
+        let cloud = Sphere::new()
+            .with_transform(Matrix::new().scale_u(2).translate(0, 5, 0))
+            .with_material(Material::new().with_medium(medium));
+        let w = World::new()
+            .with_lights(vec![PointLight::new(Point::new(0, 10, 0), color::WHITE)])
+            .with_objects(vec![floor, cloud]);
+        let r = Ray::new(Point::new(0, 1.01, -1), Vector::new(0, -1, 1).norm());
+        let mut rng = Rng::new(13);
+
+        let c = Integrator::PathTracing { max_depth: 1 }.color_at(&w, r, &mut rng);
+
+        // the light passes through 4 units of the medium
+        assert_eq!(c, w.color_at(r));
+        assert_eq!(c, color::WHITE * 0.9 * medium.transmittance(4.0));
+    }
+
+    #[test]
+    fn indirect_light_brightens_surfaces_facing_away_from_the_light() {
+        // the floor is lit from above; the underside of the sphere above it
+        // is only lit by light bouncing off the floor
+        let floor = Sphere::new().with_transform(Matrix::new().scale(100, 0.01, 100));
+        let ball = Sphere::new().with_transform(Matrix::new().translate(0, 2, 0));
+        let w = World::new()
+            .with_lights(vec![PointLight::new(Point::new(0, 10, 0), color::WHITE)])
+            .with_objects(vec![floor, ball]);
+        let r = Ray::new(Point::new(0, 0.5, -5), Vector::new(0, 1.0, 5.0).norm());
+        let mut rng = Rng::new(2);
+
+        let direct = Integrator::PathTracing { max_depth: 1 }.color_at(&w, r, &mut rng);
+        let total = (0..200)
+            .map(|_| Integrator::PathTracing { max_depth: 8 }.color_at(&w, r, &mut rng))
+            .fold(color::BLACK, |acc, c| acc + c);
+
+        assert_eq!(direct, color::BLACK);
+        assert!((total / 200).red() > 0.05);
+    }
+}
@@ -28,16 +28,17 @@ impl<'a> Intersection<'a> {
         self.object
     }
 
-    pub fn compute_state(&self, ray: Ray) -> IntersectionState {
+    pub fn compute_state(&self, ray: Ray) -> IntersectionState<'a> {
         let point = ray.position(self.t);
         let eye_v = -ray.direction();
-        let normal_v = self.object.normal_at(point);
+        let normal_v = self.object.normal_at_time(point, ray.time());
         let inside = normal_v.dot(eye_v) < 0.0;
         let normal_v = if inside { -normal_v } else { normal_v };
         let over_point = point + normal_v * EPSILON;
 
         IntersectionState {
             t: self.t,
+            time: ray.time(),
             object: self.object,
             point,
             eye_v,
@@ -77,7 +78,7 @@ impl<'a> Intersections<'a> {
         self.xs.is_empty()
     }
 
-    pub fn iter(&self) -> std::slice::Iter<Intersection<'a>> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Intersection<'a>> {
         self.xs.iter()
     }
 
@@ -127,6 +128,7 @@ impl<'a> FromIterator<Intersection<'a>> for Intersections<'a> {
 
 pub struct IntersectionState<'a> {
     t: f64,
+    time: f64,
     object: &'a Shape,
     point: Point,
     eye_v: Vector,
@@ -136,12 +138,41 @@ pub struct IntersectionState<'a> {
 }
 
 impl<'a> IntersectionState<'a> {
-    pub fn lighting(&'a self, light: PointLight, in_shadow: bool) -> Color {
+    pub fn lighting(&self, light: PointLight, in_shadow: bool) -> Color {
         self.object
             .material()
             .lighting(light, self.point, self.eye_v, self.normal_v, in_shadow)
     }
 
+    pub fn t(&self) -> f64 {
+        self.t
+    }
+
+    /// The time of the ray that produced this intersection.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn object(&self) -> &'a Shape {
+        self.object
+    }
+
+    pub fn point(&self) -> Point {
+        self.point
+    }
+
+    pub fn eye_v(&self) -> Vector {
+        self.eye_v
+    }
+
+    pub fn normal_v(&self) -> Vector {
+        self.normal_v
+    }
+
+    pub fn inside(&self) -> bool {
+        self.inside
+    }
+
     pub(crate) fn over_point(&self) -> Point {
         self.over_point
     }
@@ -155,7 +186,7 @@ mod tests {
     use crate::{
         geometry::{Matrix, Point, Tuple, Vector},
         raytracer::Ray,
-        shape::Sphere,
+        shape::{Bump, Sphere},
         EPSILON,
     };
 
@@ -251,6 +282,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_comparison)]
     fn the_hit_when_an_intersection_occurs_on_the_outside() {
         let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
         let shape = Sphere::new();
@@ -262,6 +294,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn the_hit_when_an_intersection_occurs_on_the_inside() {
         let r = Ray::new(Point::new(0, 0, 0), Vector::new(0, 0, 1));
         let shape = Sphere::new();
@@ -287,4 +320,20 @@ mod tests {
         assert!(comps.over_point.z() < -EPSILON / 2.0);
         assert!(comps.point.z() > comps.over_point.z());
     }
+
+    #[test]
+    fn the_state_uses_the_perturbed_normal() {
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+        let shape = Sphere::new().with_bump(Bump::height(|p: Point| p.x(), 1.0));
+        let i = Intersection::new(4, &shape);
+
+        let comps = i.compute_state(r);
+
+        assert_eq!(comps.normal_v, Vector::new(-1, 0, -1).norm());
+        assert!(!comps.inside);
+        assert_eq!(
+            comps.over_point,
+            comps.point + Vector::new(-1, 0, -1).norm() * EPSILON
+        );
+    }
 }
@@ -1,6 +1,6 @@
 use crate::geometry::{Point, Vector};
 
-use super::{color, Color, PointLight};
+use super::{color, Brdf, Color, Medium, Microfacet, PointLight, Rng, Shading};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Material {
@@ -9,6 +9,9 @@ pub struct Material {
     diffuse: f64,
     specular: f64,
     shininess: f64,
+    emission: Color,
+    shading: Shading,
+    medium: Option<Medium>,
 }
 
 impl Material {
@@ -19,9 +22,20 @@ impl Material {
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
+            emission: color::BLACK,
+            shading: Shading::Phong,
+            medium: None,
         }
     }
 
+    /// Returns a physically based material with the given base color,
+    /// metalness and roughness, as used by most modern authoring tools.
+    pub fn pbr(base_color: Color, metallic: f64, roughness: f64) -> Material {
+        Material::new()
+            .with_color(base_color)
+            .with_shading(Shading::Microfacet(Microfacet::new(metallic, roughness)))
+    }
+
     pub fn with_color(mut self, color: Color) -> Self {
         self.color = color;
         self
@@ -47,6 +61,25 @@ impl Material {
         self
     }
 
+    /// Sets the light given off by the surface itself, independent of any
+    /// light falling on it.
+    pub fn with_emission(mut self, emission: Color) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    pub fn with_shading(mut self, shading: Shading) -> Self {
+        self.shading = shading;
+        self
+    }
+
+    /// Fills the shape with a participating medium. The shape's surface
+    /// becomes an invisible boundary, and the shape must be closed.
+    pub fn with_medium(mut self, medium: Medium) -> Self {
+        self.medium = Some(medium);
+        self
+    }
+
     pub fn color(&self) -> Color {
         self.color
     }
@@ -67,6 +100,43 @@ impl Material {
         self.shininess
     }
 
+    pub fn emission(&self) -> Color {
+        self.emission
+    }
+
+    pub fn medium(&self) -> Option<Medium> {
+        self.medium
+    }
+
+    pub fn shading(&self) -> Shading {
+        self.shading
+    }
+
+    /// Returns the BRDF of the material's shading model: how much of the
+    /// light arriving from `light_v` is reflected towards `eye_v`, per unit
+    /// solid angle. The vectors must be normalized and point away from the
+    /// surface.
+    pub fn brdf(&self, normal_v: Vector, eye_v: Vector, light_v: Vector) -> Color {
+        self.shading.eval(self, normal_v, eye_v, light_v)
+    }
+
+    /// Picks a direction to follow light back along from the surface; see
+    /// `Brdf::sample`.
+    pub fn sample_brdf(
+        &self,
+        normal_v: Vector,
+        eye_v: Vector,
+        rng: &mut Rng,
+    ) -> Option<(Vector, Color)> {
+        self.shading.sample(self, normal_v, eye_v, rng)
+    }
+
+    // The ambient contribution: the surface color combined with the light's
+    // color/intensity, scaled by the ambient factor.
+    pub(crate) fn ambient_light(&self, light: PointLight) -> Color {
+        self.color * light.intensity() * self.ambient
+    }
+
     pub fn lighting(
         &self,
         light: PointLight,
@@ -75,20 +145,16 @@ impl Material {
         normalv: Vector,
         in_shadow: bool,
     ) -> Color {
-        // combine the surface color with the light's color/intensity
-        let effective_color = self.color * light.intensity();
-
-        // find the direction to the light source
-        let lightv = (light.position() - point).norm();
-
-        // compute the ambient contribution
-        let ambient = effective_color * self.ambient;
+        let ambient = self.ambient_light(light);
 
-        // ignore diffuse and specular components if in shadow
+        // ignore the reflected light if in shadow
         if in_shadow {
             return ambient;
         }
 
+        // find the direction to the light source
+        let lightv = (light.position() - point).norm();
+
         // light_dot_normal represents the cosine of the angle between the
         // light vector and the normal vector. A negative number means the
         // light is on the other side of the surface.
@@ -97,25 +163,7 @@ impl Material {
             return ambient;
         }
 
-        // compute the diffuse contribution
-        let diffuse = effective_color * self.diffuse * light_dot_normal;
-
-        // reflect_dot_eye represents the cosine of the angle between the
-        // reflection vector and the eye vector. A negative number means the
-        // light reflects away from the eye.
-        let reflectv = (-lightv).reflect(normalv);
-        let reflect_dot_eye = reflectv.dot(eyev);
-
-        let specular = if reflect_dot_eye > 0.0 {
-            // compute the specular contribution
-            let factor = reflect_dot_eye.powf(self.shininess);
-            light.intensity() * (self.specular * factor)
-        } else {
-            color::BLACK
-        };
-
-        // Add the three contributions together to get the final shading
-        ambient + diffuse + specular
+        ambient + self.shading.shade(self, normalv, eyev, lightv) * light.intensity()
     }
 }
 
@@ -130,6 +178,7 @@ impl Default for Material {
 #[cfg(test)]
 mod tests {
     use std::f32::consts::SQRT_2;
+    use std::f64::consts::PI;
 
     use super::*;
     use crate::{
@@ -150,6 +199,67 @@ mod tests {
         assert_eq!(m.diffuse, 0.9);
         assert_eq!(m.specular, 0.9);
         assert_eq!(m.shininess, 200.0);
+        assert_eq!(m.emission, color::BLACK);
+        assert_eq!(m.shading, Shading::Phong);
+        assert_eq!(m.medium, None);
+    }
+
+    #[test]
+    fn a_pbr_material() {
+        let m = Material::pbr(Color::new(1, 0.5, 0.25), 1.0, 0.3);
+
+        assert_eq!(m.color(), Color::new(1, 0.5, 0.25));
+        assert_eq!(m.shading(), Shading::Microfacet(Microfacet::new(1.0, 0.3)));
+    }
+
+    #[test]
+    fn lighting_a_rough_dielectric_head_on() {
+        let m = Material::pbr(color::WHITE, 0.0, 1.0);
+        let eyev = Vector::new(0, 0, -1);
+        let normalv = Vector::new(0, 0, -1);
+        let light = PointLight::new(Point::new(0, 0, -10), Color::new(1.0, 1.0, 1.0));
+
+        let result = m.lighting(light, Point::new(0, 0, 0), eyev, normalv, false);
+
+        // ambient, plus PI times the BRDF
+        assert_eq!(result, Color::new(1.20679, 1.20679, 1.20679));
+    }
+
+    #[test]
+    fn lighting_a_pbr_material_in_shadow() {
+        let m = Material::pbr(color::WHITE, 1.0, 0.5);
+        let eyev = Vector::new(0, 0, -1);
+        let normalv = Vector::new(0, 0, -1);
+        let light = PointLight::new(Point::new(0, 0, -10), Color::new(1.0, 1.0, 1.0));
+
+        let result = m.lighting(light, Point::new(0, 0, 0), eyev, normalv, true);
+
+        assert_eq!(result, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn the_phong_brdf_is_lambertian() {
+        let m = Material::new();
+        let n = Vector::new(0, 0, 1);
+
+        let f = m.brdf(n, n, Vector::new(0, 1, 1).norm());
+
+        assert_eq!(f * PI, Color::new(0.9, 0.9, 0.9));
+    }
+
+    #[test]
+    fn the_brdf_is_zero_for_light_from_behind_the_surface() {
+        let m = Material::new();
+        let n = Vector::new(0, 0, 1);
+
+        assert_eq!(m.brdf(n, n, Vector::new(0, 1, -1).norm()), color::BLACK);
+    }
+
+    #[test]
+    fn a_material_may_be_emissive() {
+        let m = Material::new().with_emission(Color::new(2, 1, 0.5));
+
+        assert_eq!(m.emission(), Color::new(2, 1, 0.5));
     }
 
     #[test]
@@ -212,6 +322,20 @@ mod tests {
         assert_eq!(result, Color::new(0.1, 0.1, 0.1));
     }
 
+    #[test]
+    fn lighting_with_the_light_at_right_angles_to_the_surface() {
+        // the light grazes the surface, so there's no diffuse light, but the
+        // eye is in the path of the reflection vector
+        let (m, position) = background();
+        let eyev = Vector::new(0, -1, 0);
+        let normalv = Vector::new(0, 0, -1);
+        let light = PointLight::new(Point::new(0, 10, 0), Color::new(1.0, 1.0, 1.0));
+
+        let result = m.lighting(light, position, eyev, normalv, false);
+
+        assert_eq!(result, Color::new(1.0, 1.0, 1.0));
+    }
+
     #[test]
     fn lighting_with_the_surface_in_shadow() {
         let (m, position) = background();
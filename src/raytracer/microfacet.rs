@@ -0,0 +1,314 @@
+use std::f64::consts::PI;
+
+use crate::geometry::Vector;
+use crate::raytracer::{color, sampling, Brdf, Color, Material, Rng};
+
+/// The reflectance of dielectrics (plastics, paint, wood...) at normal
+/// incidence.
+const DIELECTRIC_F0: f64 = 0.04;
+
+/// The smallest GGX alpha used, since a perfectly smooth surface would only
+/// reflect in a single direction, and never reflect a point light.
+const MIN_ALPHA: f64 = 1e-3;
+
+/// A physically based, metallic-roughness surface: a Cook-Torrance specular
+/// lobe with a GGX distribution of microfacet normals, Smith masking and
+/// shadowing, and Schlick's Fresnel approximation, over a Lambertian base.
+///
+/// Metals (`metallic` 1) have no diffuse base, and reflect specularly in the
+/// base color. Dielectrics (`metallic` 0) reflect about 4% specularly,
+/// without tinting (more at grazing angles), and diffusely reflect the rest
+/// in the base color. The light reflected specularly is taken out of the
+/// diffuse base, so the surface never reflects more light than it receives.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Microfacet {
+    metallic: f64,
+    roughness: f64,
+}
+
+impl Microfacet {
+    /// Both `metallic` and `roughness` are clamped to [0, 1]. The roughness
+    /// is perceptual: GGX's alpha is its square.
+    pub fn new(metallic: f64, roughness: f64) -> Microfacet {
+        Microfacet {
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn metallic(&self) -> f64 {
+        self.metallic
+    }
+
+    pub fn roughness(&self) -> f64 {
+        self.roughness
+    }
+
+    fn alpha(&self) -> f64 {
+        (self.roughness * self.roughness).max(MIN_ALPHA)
+    }
+
+    // The reflectance at normal incidence.
+    fn f0(&self, base_color: Color) -> Color {
+        let dielectric = Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0);
+        dielectric * (1.0 - self.metallic) + base_color * self.metallic
+    }
+
+    // Metals only have a specular lobe; dielectrics split samples evenly
+    // between the two lobes.
+    fn specular_probability(&self) -> f64 {
+        0.5 + 0.5 * self.metallic
+    }
+
+    fn sample_half_vector(&self, normal_v: Vector, rng: &mut Rng) -> Vector {
+        let alpha = self.alpha();
+        let u = rng.next_f64();
+        let phi = 2.0 * PI * rng.next_f64();
+        let cos_theta = ((1.0 - u) / (1.0 + (alpha * alpha - 1.0) * u)).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+
+        let (tangent, bitangent) = sampling::orthonormal_basis(normal_v);
+        tangent * (sin_theta * phi.cos())
+            + bitangent * (sin_theta * phi.sin())
+            + normal_v * cos_theta
+    }
+
+    fn pdf(&self, normal_v: Vector, eye_v: Vector, light_v: Vector) -> f64 {
+        let half_v = (eye_v + light_v).norm();
+        let n_dot_h = normal_v.dot(half_v);
+        let v_dot_h = eye_v.dot(half_v);
+
+        let specular = if n_dot_h > 0.0 && v_dot_h > 0.0 {
+            ggx_distribution(n_dot_h, self.alpha()) * n_dot_h / (4.0 * v_dot_h)
+        } else {
+            0.0
+        };
+        let diffuse = normal_v.dot(light_v).max(0.0) / PI;
+
+        let p = self.specular_probability();
+        p * specular + (1.0 - p) * diffuse
+    }
+}
+
+impl Brdf for Microfacet {
+    fn eval(&self, material: &Material, normal_v: Vector, eye_v: Vector, light_v: Vector) -> Color {
+        let base_color = material.color();
+        let n_dot_l = normal_v.dot(light_v);
+        let n_dot_v = normal_v.dot(eye_v);
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return color::BLACK;
+        }
+
+        let half_v = (eye_v + light_v).norm();
+        let n_dot_h = normal_v.dot(half_v);
+        let v_dot_h = eye_v.dot(half_v);
+        let alpha = self.alpha();
+
+        let f0 = self.f0(base_color);
+        let fresnel = fresnel_schlick(f0, v_dot_h);
+        let d = ggx_distribution(n_dot_h, alpha);
+        let g = smith_g1(n_dot_l, alpha) * smith_g1(n_dot_v, alpha);
+        let specular = fresnel * (d * g / (4.0 * n_dot_l * n_dot_v));
+
+        // Ashikhmin and Shirley's diffuse term, which accounts for the light
+        // reflected specularly at both the incoming and outgoing angles
+        let diffuse = (color::WHITE - f0)
+            * base_color
+            * ((1.0 - self.metallic) * 28.0 / (23.0 * PI)
+                * (1.0 - (1.0 - n_dot_l / 2.0).powi(5))
+                * (1.0 - (1.0 - n_dot_v / 2.0).powi(5)));
+
+        diffuse + specular
+    }
+
+    // Samples the specular lobe and the diffuse base in proportion.
+    fn sample(
+        &self,
+        material: &Material,
+        normal_v: Vector,
+        eye_v: Vector,
+        rng: &mut Rng,
+    ) -> Option<(Vector, Color)> {
+        let light_v = if rng.next_f64() < self.specular_probability() {
+            let half_v = self.sample_half_vector(normal_v, rng);
+            -eye_v.reflect(half_v)
+        } else {
+            sampling::cosine_hemisphere(normal_v, rng)
+        };
+
+        let n_dot_l = normal_v.dot(light_v);
+        if n_dot_l <= 0.0 {
+            return None;
+        }
+
+        let pdf = self.pdf(normal_v, eye_v, light_v);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        let f = self.eval(material, normal_v, eye_v, light_v);
+        Some((light_v, f * (n_dot_l / pdf)))
+    }
+}
+
+fn ggx_distribution(n_dot_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+fn smith_g1(n_dot_v: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    2.0 * n_dot_v / (n_dot_v + (a2 + (1.0 - a2) * n_dot_v * n_dot_v).sqrt())
+}
+
+fn fresnel_schlick(f0: Color, cos_theta: f64) -> Color {
+    let weight = (1.0 - cos_theta).clamp(0.0, 1.0).powi(5);
+    f0 + (color::WHITE - f0) * weight
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Estimates the fraction of light arriving from `eye_v` that's reflected,
+    // using uniform sampling of the hemisphere, and then using the surface's
+    // own importance sampling.
+    fn albedo(m: Microfacet, base_color: Color, eye_v: Vector) -> (f64, f64) {
+        let n = Vector::new(0, 0, 1);
+        let mut rng = Rng::new(7);
+        let count = 100000;
+
+        let mut uniform = 0.0;
+        let mut sampled = 0.0;
+        for _ in 0..count {
+            let z = rng.next_f64();
+            let r = (1.0 - z * z).sqrt();
+            let phi = 2.0 * PI * rng.next_f64();
+            let l = Vector::new(r * phi.cos(), r * phi.sin(), z);
+            uniform += m
+                .eval(&Material::new().with_color(base_color), n, eye_v, l)
+                .red()
+                * z
+                * 2.0
+                * PI;
+
+            if let Some((_, weight)) =
+                m.sample(&Material::new().with_color(base_color), n, eye_v, &mut rng)
+            {
+                sampled += weight.red();
+            }
+        }
+
+        (uniform / count as f64, sampled / count as f64)
+    }
+
+    #[test]
+    fn a_rough_dielectric_viewed_head_on() {
+        let m = Microfacet::new(0.0, 1.0);
+        let n = Vector::new(0, 0, 1);
+
+        let f = m.eval(&Material::new().with_color(color::WHITE), n, n, n);
+
+        // a diffuse term of (28/23) * 0.96 * (1 - 0.5^5)^2, plus a specular
+        // lobe of D * F / 4 = (1 / PI) * 0.04 / 4
+        assert_eq!(f * PI, Color::new(1.10679, 1.10679, 1.10679));
+    }
+
+    #[test]
+    fn a_metal_reflects_in_its_base_color() {
+        let m = Microfacet::new(1.0, 0.5);
+        let n = Vector::new(0, 0, 1);
+        let gold = Color::new(1.0, 0.78, 0.34);
+
+        let f = m.eval(&Material::new().with_color(gold), n, n, n);
+
+        // there's no diffuse base, and at normal incidence F is the base color
+        let d = ggx_distribution(1.0, 0.25);
+        assert_eq!(f, gold * (d / 4.0));
+    }
+
+    #[test]
+    fn nothing_is_reflected_from_below_the_surface() {
+        let m = Microfacet::new(0.0, 0.5);
+        let n = Vector::new(0, 0, 1);
+
+        let f = m.eval(
+            &Material::new().with_color(color::WHITE),
+            n,
+            n,
+            Vector::new(0, 1, -1).norm(),
+        );
+
+        assert_eq!(f, color::BLACK);
+    }
+
+    #[test]
+    fn smoother_surfaces_have_sharper_highlights() {
+        let n = Vector::new(0, 0, 1);
+        let v = Vector::new(0, 1, 1).norm();
+        let mirror = Vector::new(0, -1, 1).norm();
+        let off = Vector::new(0, -1, 2).norm();
+
+        let smooth = Microfacet::new(1.0, 0.1);
+        let rough = Microfacet::new(1.0, 0.8);
+
+        assert!(
+            smooth
+                .eval(&Material::new().with_color(color::WHITE), n, v, mirror)
+                .red()
+                > rough
+                    .eval(&Material::new().with_color(color::WHITE), n, v, mirror)
+                    .red()
+        );
+        assert!(
+            smooth
+                .eval(&Material::new().with_color(color::WHITE), n, v, off)
+                .red()
+                < rough
+                    .eval(&Material::new().with_color(color::WHITE), n, v, off)
+                    .red()
+        );
+    }
+
+    #[test]
+    fn surfaces_never_reflect_more_light_than_they_receive() {
+        let eyes = [
+            Vector::new(0, 0, 1),
+            Vector::new(0, 1, 1).norm(),
+            Vector::new(1, 0, 0.1).norm(),
+        ];
+
+        for (metallic, roughness) in [(0.0, 0.2), (0.0, 1.0), (1.0, 0.3), (1.0, 1.0), (0.5, 0.6)] {
+            for eye_v in eyes {
+                let (uniform, _) =
+                    albedo(Microfacet::new(metallic, roughness), color::WHITE, eye_v);
+                assert!(
+                    uniform <= 1.01,
+                    "{} {} reflects {}",
+                    metallic,
+                    roughness,
+                    uniform
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn importance_sampling_agrees_with_uniform_sampling() {
+        let eye_v = Vector::new(0, 1, 2).norm();
+
+        for (metallic, roughness) in [(0.0, 0.5), (1.0, 0.4), (0.3, 0.9)] {
+            let (uniform, sampled) =
+                albedo(Microfacet::new(metallic, roughness), color::WHITE, eye_v);
+            assert!(
+                (uniform - sampled).abs() < 0.02,
+                "{} vs {}",
+                uniform,
+                sampled
+            );
+        }
+    }
+}
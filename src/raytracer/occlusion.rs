@@ -0,0 +1,157 @@
+use crate::geometry::{Point, Tuple, Vector};
+use crate::raytracer::{sampling, Ray, Rng, World};
+
+/// Settings for estimating ambient occlusion: how much of the sky above a
+/// point is hidden by nearby objects, so that creases and contact points
+/// receive less ambient light.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AmbientOcclusion {
+    samples: usize,
+    max_distance: f64,
+}
+
+impl AmbientOcclusion {
+    /// Casts `samples` rays from each point, over the hemisphere around its
+    /// normal. Only objects within `max_distance` of the point occlude it.
+    pub fn new(samples: usize, max_distance: f64) -> AmbientOcclusion {
+        AmbientOcclusion {
+            samples: samples.max(1),
+            max_distance,
+        }
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    pub fn max_distance(&self) -> f64 {
+        self.max_distance
+    }
+
+    /// Returns the fraction of the hemisphere around `normal` that isn't
+    /// occluded, weighted by the cosine to the normal: 1 for a point in the
+    /// open, and 0 for one enclosed on all sides.
+    pub fn unoccluded(
+        &self,
+        world: &World,
+        point: Point,
+        normal: Vector,
+        time: f64,
+        rng: &mut Rng,
+    ) -> f64 {
+        let open = (0..self.samples)
+            .filter(|_| {
+                let direction = sampling::cosine_hemisphere(normal, rng);
+                let ray = Ray::new(point, direction)
+                    .with_interval(0.0, self.max_distance)
+                    .with_time(time);
+                !world.is_occluded(ray)
+            })
+            .count();
+
+        open as f64 / self.samples as f64
+    }
+
+    /// Like `unoccluded`, but with the rays chosen by hashing the point, so
+    /// the estimate is repeatable without a random number generator.
+    pub(crate) fn unoccluded_at(
+        &self,
+        world: &World,
+        point: Point,
+        normal: Vector,
+        time: f64,
+    ) -> f64 {
+        let seed = point.x().to_bits()
+            ^ point.y().to_bits().rotate_left(21)
+            ^ point.z().to_bits().rotate_left(42);
+        self.unoccluded(world, point, normal, time, &mut Rng::new(seed))
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{geometry::Matrix, shape::Sphere};
+
+    fn floor() -> Sphere {
+        Sphere::new().with_transform(Matrix::new().scale(100, 0.01, 100).translate(0, -0.01, 0))
+    }
+
+    #[test]
+    fn a_point_in_the_open_is_unoccluded() {
+        let w = World::new().with_objects(vec![floor()]);
+        let ao = AmbientOcclusion::new(64, 10.0);
+        let mut rng = Rng::new(0);
+
+        let open = ao.unoccluded(
+            &w,
+            Point::new(0, 0.0001, 0),
+            Vector::new(0, 1, 0),
+            0.0,
+            &mut rng,
+        );
+
+        assert_eq!(open, 1.0);
+    }
+
+    #[test]
+    fn a_point_inside_a_sphere_is_fully_occluded() {
+        let w = World::new().with_objects(vec![Sphere::new()]);
+        let ao = AmbientOcclusion::new(64, 10.0);
+        let mut rng = Rng::new(0);
+
+        let open = ao.unoccluded(&w, Point::new(0, 0, 0), Vector::new(0, 1, 0), 0.0, &mut rng);
+
+        assert_eq!(open, 0.0);
+    }
+
+    #[test]
+    fn a_point_next_to_an_object_is_partly_occluded() {
+        let ball = Sphere::new().with_transform(Matrix::new().translate(0, 1, 0));
+        let w = World::new().with_objects(vec![floor(), ball]);
+        let ao = AmbientOcclusion::new(256, 10.0);
+        let mut rng = Rng::new(0);
+
+        let open = ao.unoccluded(
+            &w,
+            Point::new(0.5, 0.0001, 0),
+            Vector::new(0, 1, 0),
+            0.0,
+            &mut rng,
+        );
+
+        assert!(open > 0.1 && open < 0.9, "open was {}", open);
+    }
+
+    #[test]
+    fn objects_beyond_the_maximum_distance_do_not_occlude() {
+        let ceiling =
+            Sphere::new().with_transform(Matrix::new().scale(100, 0.01, 100).translate(0, 5, 0));
+        let w = World::new().with_objects(vec![floor(), ceiling]);
+        let mut rng = Rng::new(0);
+        let p = Point::new(0, 0.0001, 0);
+        let up = Vector::new(0, 1, 0);
+
+        let near = AmbientOcclusion::new(64, 2.0).unoccluded(&w, p, up, 0.0, &mut rng);
+        let far = AmbientOcclusion::new(64, 1000.0).unoccluded(&w, p, up, 0.0, &mut rng);
+
+        assert_eq!(near, 1.0);
+        assert_eq!(far, 0.0);
+    }
+
+    #[test]
+    fn hashed_estimates_are_repeatable() {
+        let ball = Sphere::new().with_transform(Matrix::new().translate(0, 1, 0));
+        let w = World::new().with_objects(vec![floor(), ball]);
+        let ao = AmbientOcclusion::new(16, 10.0);
+        let p = Point::new(0.5, 0.0001, 0);
+        let up = Vector::new(0, 1, 0);
+
+        assert_eq!(
+            ao.unoccluded_at(&w, p, up, 0.0),
+            ao.unoccluded_at(&w, p, up, 0.0)
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use crate::geometry::{Point, Vector};
+use crate::raytracer::{IntersectionState, Material, ObjectId};
+
+/// Describes what a camera sees at a single pixel: the object that was hit,
+/// and where and how it was hit.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Pick {
+    object: ObjectId,
+    point: Point,
+    normal: Vector,
+    distance: f64,
+    material: Material,
+}
+
+impl Pick {
+    pub(crate) fn new(object: ObjectId, state: &IntersectionState) -> Pick {
+        Pick {
+            object,
+            point: state.point(),
+            normal: state.normal_v(),
+            distance: state.t(),
+            material: state.object().material(),
+        }
+    }
+
+    /// The handle of the hit object in the world.
+    pub fn object(&self) -> ObjectId {
+        self.object
+    }
+
+    /// The world-space point where the pixel's ray hit the object.
+    pub fn point(&self) -> Point {
+        self.point
+    }
+
+    /// The world-space surface normal at the hit, facing the camera.
+    pub fn normal(&self) -> Vector {
+        self.normal
+    }
+
+    /// The distance along the pixel's ray to the hit.
+    pub fn distance(&self) -> f64 {
+        self.distance
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+}
@@ -0,0 +1,265 @@
+extern crate image;
+
+use crate::raytracer::{Canvas, Color};
+use image::{ImageBuffer, ImageResult, Rgb, RgbImage};
+
+/// Operators that compress unbounded scene radiance into the displayable
+/// range `[0, 1]`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ToneMap {
+    /// Clip each channel at 1. This is what `Canvas::save` does.
+    Clamp,
+    /// `x / (1 + x)`: never clips, but desaturates highlights.
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic reference curve.
+    Aces,
+    /// John Hable's "Uncharted 2" filmic curve, with a white point of 11.2.
+    Filmic,
+}
+
+impl ToneMap {
+    fn map(&self, x: f64) -> f64 {
+        let x = x.max(0.0);
+        match self {
+            ToneMap::Clamp => x.min(1.0),
+            ToneMap::Reinhard => x / (1.0 + x),
+            ToneMap::Aces => {
+                let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                ((x * (a * x + b)) / (x * (c * x + d) + e)).clamp(0.0, 1.0)
+            }
+            ToneMap::Filmic => {
+                const WHITE_POINT: f64 = 11.2;
+                (hable(x) / hable(WHITE_POINT)).min(1.0)
+            }
+        }
+    }
+}
+
+fn hable(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+/// The sRGB transfer function, mapping linear intensity in `[0, 1]` to
+/// gamma-encoded values in `[0, 1]`.
+fn srgb_encode(x: f64) -> f64 {
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// The steps taken to turn the linear radiance stored in a `Canvas` into
+/// display-ready colors: exposure, then tone mapping, then (optionally)
+/// sRGB encoding.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PostProcess {
+    exposure: f64,
+    tone_map: ToneMap,
+    srgb: bool,
+}
+
+impl PostProcess {
+    /// Returns a pipeline that leaves colors as they are, except for
+    /// clamping them to `[0, 1]`.
+    pub fn new() -> PostProcess {
+        PostProcess {
+            exposure: 0.0,
+            tone_map: ToneMap::Clamp,
+            srgb: false,
+        }
+    }
+
+    /// Sets the exposure adjustment in stops; each stop doubles the
+    /// brightness of the image.
+    pub fn with_exposure(mut self, exposure: f64) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    pub fn with_tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn exposure(&self) -> f64 {
+        self.exposure
+    }
+
+    pub fn tone_map(&self) -> ToneMap {
+        self.tone_map
+    }
+
+    pub fn srgb(&self) -> bool {
+        self.srgb
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        let scale = self.exposure.exp2();
+        let map = |x: f64| {
+            let mapped = self.tone_map.map(x * scale);
+            if self.srgb {
+                srgb_encode(mapped)
+            } else {
+                mapped
+            }
+        };
+        Color::new(map(color.red()), map(color.green()), map(color.blue()))
+    }
+
+    /// Applies the pipeline and quantizes the result to 8 bits, rounding to
+    /// the nearest value.
+    pub fn to_rgb(&self, color: Color) -> Rgb<u8> {
+        let c = self.apply(color);
+        let quantize = |x: f64| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Rgb([quantize(c.red()), quantize(c.green()), quantize(c.blue())])
+    }
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+impl Canvas {
+    pub fn post_process(&self, post: &PostProcess) -> Canvas {
+        let mut result = Canvas::new(self.width(), self.height());
+        for col in 0..self.width() {
+            for row in 0..self.height() {
+                result.set(col, row, post.apply(self.get(col, row)));
+            }
+        }
+        result
+    }
+
+    pub fn save_with(&self, path: &str, post: &PostProcess) -> ImageResult<()> {
+        let mut image: RgbImage = ImageBuffer::new(self.width() as u32, self.height() as u32);
+        for (col, row, pixel) in image.enumerate_pixels_mut() {
+            *pixel = post.to_rgb(self.get(col as usize, row as usize))
+        }
+        image.save(path)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feq;
+
+    #[test]
+    fn the_default_post_process_only_clamps() {
+        let post = PostProcess::new();
+
+        assert_eq!(
+            post.apply(Color::new(-0.5, 0.25, 1.5)),
+            Color::new(0, 0.25, 1)
+        );
+    }
+
+    #[test]
+    fn exposure_is_measured_in_stops() {
+        let post = PostProcess::new().with_exposure(1.0);
+
+        assert_eq!(
+            post.apply(Color::new(0.25, 0.1, 0.4)),
+            Color::new(0.5, 0.2, 0.8)
+        );
+
+        let post = PostProcess::new().with_exposure(-2.0);
+
+        assert_eq!(
+            post.apply(Color::new(1, 0.5, 2)),
+            Color::new(0.25, 0.125, 0.5)
+        );
+    }
+
+    #[test]
+    fn reinhard_tone_mapping() {
+        let post = PostProcess::new().with_tone_map(ToneMap::Reinhard);
+
+        assert_eq!(post.apply(Color::new(0, 1, 3)), Color::new(0, 0.5, 0.75));
+    }
+
+    #[test]
+    fn aces_tone_mapping() {
+        let post = PostProcess::new().with_tone_map(ToneMap::Aces);
+
+        let c = post.apply(Color::new(0, 0.18, 100));
+
+        assert_eq!(c, Color::new(0, 0.26690, 1));
+    }
+
+    #[test]
+    fn filmic_tone_mapping_maps_the_white_point_to_one() {
+        let post = PostProcess::new().with_tone_map(ToneMap::Filmic);
+
+        let c = post.apply(Color::new(0, 11.2, 50));
+
+        assert!(feq(c.red(), 0.0));
+        assert!(feq(c.green(), 1.0));
+        assert!(feq(c.blue(), 1.0));
+    }
+
+    #[test]
+    fn tone_mapping_operators_are_monotonic() {
+        for tone_map in [ToneMap::Reinhard, ToneMap::Aces, ToneMap::Filmic] {
+            let mut last = 0.0;
+            for i in 1..100 {
+                let x = tone_map.map(i as f64 * 0.1);
+                assert!(x >= last, "{:?} is not monotonic at {}", tone_map, i);
+                assert!(x <= 1.0);
+                last = x;
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_encoding() {
+        assert!(feq(srgb_encode(0.0), 0.0));
+        assert!(feq(srgb_encode(0.002), 0.02584));
+        assert!(feq(srgb_encode(0.18), 0.46135));
+        assert!(feq(srgb_encode(1.0), 1.0));
+    }
+
+    #[test]
+    fn quantizing_rounds_to_the_nearest_value() {
+        let post = PostProcess::new();
+
+        assert_eq!(
+            post.to_rgb(Color::new(0.5, 0.999, 0.001)),
+            Rgb([128, 255, 0])
+        );
+    }
+
+    #[test]
+    fn quantizing_with_srgb_encoding() {
+        let post = PostProcess::new().with_srgb(true);
+
+        assert_eq!(post.to_rgb(Color::new(0.18, 0.5, 1)), Rgb([118, 188, 255]));
+    }
+
+    #[test]
+    fn post_processing_a_canvas() {
+        let mut c = Canvas::new(2, 1);
+        c.set(1, 0, Color::new(1, 3, 0));
+        let post = PostProcess::new().with_tone_map(ToneMap::Reinhard);
+
+        let result = c.post_process(&post);
+
+        assert_eq!(result.get(0, 0), Color::new(0, 0, 0));
+        assert_eq!(result.get(1, 0), Color::new(0.5, 0.75, 0));
+    }
+}
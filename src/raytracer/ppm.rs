@@ -0,0 +1,480 @@
+use std::fmt;
+
+use crate::raytracer::{Canvas, Color};
+
+const MAX_LINE_LENGTH: usize = 70;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PpmFormat {
+    /// "P3": samples written as decimal text, wrapped at 70 characters.
+    Plain,
+    /// "P6": samples written as raw bytes.
+    Binary,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PpmError {
+    BadMagic(String),
+    UnexpectedEnd(&'static str),
+    BadNumber {
+        field: &'static str,
+        token: String,
+    },
+    BadMaxValue(u32),
+    SampleOutOfRange {
+        sample: u32,
+        max_value: u32,
+    },
+    /// The header's width and height call for more samples than the rest of
+    /// the data could hold.
+    BadSize {
+        width: usize,
+        height: usize,
+    },
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PpmError::BadMagic(magic) => {
+                write!(f, "expected \"P3\" or \"P6\", found \"{}\"", magic)
+            }
+            PpmError::UnexpectedEnd(field) => {
+                write!(f, "unexpected end of data while reading {}", field)
+            }
+            PpmError::BadNumber { field, token } => {
+                write!(f, "invalid {}: \"{}\"", field, token)
+            }
+            PpmError::BadMaxValue(max) => {
+                write!(f, "maximum value {} is not in the range 1..=65535", max)
+            }
+            PpmError::SampleOutOfRange { sample, max_value } => {
+                write!(f, "sample {} exceeds maximum value {}", sample, max_value)
+            }
+            PpmError::BadSize { width, height } => {
+                write!(f, "a {}x{} image doesn't fit in the data", width, height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PpmError {}
+
+impl Canvas {
+    pub fn to_ppm(&self, format: PpmFormat) -> Vec<u8> {
+        let magic = match format {
+            PpmFormat::Plain => "P3",
+            PpmFormat::Binary => "P6",
+        };
+        let mut out = format!("{}\n{} {}\n255\n", magic, self.width(), self.height()).into_bytes();
+
+        for row in 0..self.height() {
+            let samples = (0..self.width()).flat_map(|col| {
+                let c = self.get(col, row);
+                [c.red(), c.green(), c.blue()].map(to_byte)
+            });
+
+            match format {
+                PpmFormat::Plain => {
+                    // each row starts on a new line, and no line may be
+                    // longer than 70 characters
+                    let mut line = String::new();
+                    for sample in samples {
+                        let token = sample.to_string();
+                        if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+                            out.extend_from_slice(line.as_bytes());
+                            out.push(b'\n');
+                            line.clear();
+                        }
+                        if !line.is_empty() {
+                            line.push(' ');
+                        }
+                        line.push_str(&token);
+                    }
+                    out.extend_from_slice(line.as_bytes());
+                    out.push(b'\n');
+                }
+                PpmFormat::Binary => out.extend(samples),
+            }
+        }
+        out
+    }
+
+    pub fn save_ppm(&self, path: &str, format: PpmFormat) -> std::io::Result<()> {
+        std::fs::write(path, self.to_ppm(format))
+    }
+
+    pub fn from_ppm(data: &[u8]) -> Result<Canvas, PpmError> {
+        let mut reader = Reader { data, pos: 0 };
+
+        let magic = reader.token("magic number")?;
+        let format = match magic {
+            b"P3" => PpmFormat::Plain,
+            b"P6" => PpmFormat::Binary,
+            _ => return Err(PpmError::BadMagic(lossy(magic))),
+        };
+
+        let width = reader.number("width")? as usize;
+        let height = reader.number("height")? as usize;
+        let max_value = reader.number("maximum value")?;
+        if !(1..=65535).contains(&max_value) {
+            return Err(PpmError::BadMaxValue(max_value));
+        }
+
+        if format == PpmFormat::Binary {
+            // exactly one whitespace character separates the header from the
+            // raster
+            reader.pos += 1;
+        }
+
+        // check the size before allocating the canvas: every sample takes at
+        // least one byte, or two when binary samples exceed 255
+        let sample_size = match format {
+            PpmFormat::Binary if max_value >= 256 => 2,
+            _ => 1,
+        };
+        let remaining = data.len().saturating_sub(reader.pos);
+        let fits = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(3 * sample_size))
+            .is_some_and(|size| size <= remaining);
+        if !fits {
+            return Err(PpmError::BadSize { width, height });
+        }
+
+        let mut canvas = Canvas::new(width, height);
+        for row in 0..height {
+            for col in 0..width {
+                let mut rgb = [0.0; 3];
+                for sample in rgb.iter_mut() {
+                    let value = match format {
+                        PpmFormat::Plain => reader.number("sample")?,
+                        PpmFormat::Binary => reader.binary_sample(max_value)?,
+                    };
+                    if value > max_value {
+                        return Err(PpmError::SampleOutOfRange {
+                            sample: value,
+                            max_value,
+                        });
+                    }
+                    *sample = value as f64 / max_value as f64;
+                }
+                canvas.set(col, row, Color::new(rgb[0], rgb[1], rgb[2]));
+            }
+        }
+        Ok(canvas)
+    }
+}
+
+fn to_byte(component: f64) -> u8 {
+    (component.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+// -----------------------------------------------------------------------------
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&byte) = self.data.get(self.pos) {
+            if byte == b'#' {
+                while self.pos < self.data.len() && self.data[self.pos] != b'\n' {
+                    self.pos += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token(&mut self, field: &'static str) -> Result<&'a [u8], PpmError> {
+        self.skip_whitespace_and_comments();
+        let start = self.pos;
+        while self.pos < self.data.len()
+            && !self.data[self.pos].is_ascii_whitespace()
+            && self.data[self.pos] != b'#'
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            Err(PpmError::UnexpectedEnd(field))
+        } else {
+            Ok(&self.data[start..self.pos])
+        }
+    }
+
+    fn number(&mut self, field: &'static str) -> Result<u32, PpmError> {
+        let token = self.token(field)?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| PpmError::BadNumber {
+                field,
+                token: lossy(token),
+            })
+    }
+
+    fn binary_sample(&mut self, max_value: u32) -> Result<u32, PpmError> {
+        let size = if max_value < 256 { 1 } else { 2 };
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + size)
+            .ok_or(PpmError::UnexpectedEnd("sample"))?;
+        self.pos += size;
+        Ok(bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u32))
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(ppm: &[u8]) -> Vec<String> {
+        String::from_utf8(ppm.to_vec())
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn constructing_the_ppm_header() {
+        let c = Canvas::new(5, 3);
+
+        let ppm = lines(&c.to_ppm(PpmFormat::Plain));
+
+        assert_eq!(ppm[0..3], ["P3", "5 3", "255"]);
+    }
+
+    #[test]
+    fn constructing_the_ppm_pixel_data() {
+        let mut c = Canvas::new(5, 3);
+        c.set(0, 0, Color::new(1.5, 0, 0));
+        c.set(2, 1, Color::new(0, 0.5, 0));
+        c.set(4, 2, Color::new(-0.5, 0, 1));
+
+        let ppm = lines(&c.to_ppm(PpmFormat::Plain));
+
+        assert_eq!(
+            ppm[3..6],
+            [
+                "255 0 0 0 0 0 0 0 0 0 0 0 0 0 0",
+                "0 0 0 0 0 0 0 128 0 0 0 0 0 0 0",
+                "0 0 0 0 0 0 0 0 0 0 0 0 0 0 255",
+            ]
+        );
+    }
+
+    #[test]
+    fn splitting_long_lines_in_ppm_files() {
+        let mut c = Canvas::new(10, 2);
+        for col in 0..10 {
+            for row in 0..2 {
+                c.set(col, row, Color::new(1, 0.8, 0.6));
+            }
+        }
+
+        let ppm = lines(&c.to_ppm(PpmFormat::Plain));
+
+        assert_eq!(
+            ppm[3..7],
+            [
+                "255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204",
+                "153 255 204 153 255 204 153 255 204 153 255 204 153",
+                "255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204",
+                "153 255 204 153 255 204 153 255 204 153 255 204 153",
+            ]
+        );
+    }
+
+    #[test]
+    fn ppm_files_are_terminated_by_a_newline_character() {
+        let c = Canvas::new(5, 3);
+
+        let ppm = c.to_ppm(PpmFormat::Plain);
+
+        assert_eq!(ppm.last(), Some(&b'\n'));
+    }
+
+    #[test]
+    fn constructing_a_binary_ppm() {
+        let mut c = Canvas::new(2, 1);
+        c.set(0, 0, Color::new(1, 0.5, 0));
+        c.set(1, 0, Color::new(0, 0, 2));
+
+        let ppm = c.to_ppm(PpmFormat::Binary);
+
+        assert_eq!(ppm, b"P6\n2 1\n255\n\xff\x80\x00\x00\x00\xff");
+    }
+
+    #[test]
+    fn reading_a_file_with_the_wrong_magic_number() {
+        let ppm = b"P32\n1 1\n255\n0 0 0\n";
+
+        assert_eq!(
+            Canvas::from_ppm(ppm).err(),
+            Some(PpmError::BadMagic(String::from("P32")))
+        );
+    }
+
+    #[test]
+    fn reading_a_ppm_returns_a_canvas_of_the_right_size() {
+        let ppm = b"P3\n10 2\n255\n".to_vec();
+        let ppm = [ppm, b"0 0 0 ".repeat(20)].concat();
+
+        let c = Canvas::from_ppm(&ppm).unwrap();
+
+        assert_eq!(c.width(), 10);
+        assert_eq!(c.height(), 2);
+    }
+
+    #[test]
+    fn reading_pixel_data_from_a_ppm_file() {
+        let ppm = b"P3\n4 3\n255\n\
+            255 127 0  0 127 255  127 255 0  255 255 255\n\
+            0 0 0  255 0 0  0 255 0  0 0 255\n\
+            255 255 0  0 255 255  255 0 255  127 127 127\n";
+
+        let c = Canvas::from_ppm(ppm).unwrap();
+
+        assert_eq!(c.get(0, 0), Color::new(1, 0.49804, 0));
+        assert_eq!(c.get(1, 0), Color::new(0, 0.49804, 1));
+        assert_eq!(c.get(3, 0), Color::new(1, 1, 1));
+        assert_eq!(c.get(1, 1), Color::new(1, 0, 0));
+        assert_eq!(c.get(3, 2), Color::new(0.49804, 0.49804, 0.49804));
+    }
+
+    #[test]
+    fn ppm_parsing_ignores_comment_lines() {
+        let ppm = b"P3\n# this is a comment\n2 1\n# this, too\n255\n# another comment\n\
+            255 255 255\n# oh, no, comments in the pixel data!\n255 0 255\n";
+
+        let c = Canvas::from_ppm(ppm).unwrap();
+
+        assert_eq!(c.get(0, 0), Color::new(1, 1, 1));
+        assert_eq!(c.get(1, 0), Color::new(1, 0, 1));
+    }
+
+    #[test]
+    fn ppm_parsing_allows_an_rgb_triple_to_span_lines() {
+        let ppm = b"P3\n1 1\n255\n51\n153\n\n204\n";
+
+        let c = Canvas::from_ppm(ppm).unwrap();
+
+        assert_eq!(c.get(0, 0), Color::new(0.2, 0.6, 0.8));
+    }
+
+    #[test]
+    fn ppm_parsing_respects_the_scale_setting() {
+        let ppm = b"P3\n2 2\n100\n100 100 100  50 50 50\n75 50 25  0 0 0\n";
+
+        let c = Canvas::from_ppm(ppm).unwrap();
+
+        assert_eq!(c.get(0, 1), Color::new(0.75, 0.5, 0.25));
+    }
+
+    #[test]
+    fn reading_a_binary_ppm() {
+        let ppm = b"P6\n# comment\n2 1\n255\n\xff\x80\x00\x00\x00\xff";
+
+        let c = Canvas::from_ppm(ppm).unwrap();
+
+        assert_eq!(c.get(0, 0), Color::new(1, 0.50196, 0));
+        assert_eq!(c.get(1, 0), Color::new(0, 0, 1));
+    }
+
+    #[test]
+    fn reading_a_binary_ppm_with_two_byte_samples() {
+        let ppm = b"P6\n1 1\n65535\n\xff\xff\x80\x00\x00\x00";
+
+        let c = Canvas::from_ppm(ppm).unwrap();
+
+        assert_eq!(c.get(0, 0), Color::new(1, 0.50001, 0));
+    }
+
+    #[test]
+    fn binary_ppms_round_trip() {
+        let mut c = Canvas::new(3, 2);
+        c.set(1, 1, Color::new(1, 0, 1));
+        c.set(2, 0, Color::new(0, 1, 0));
+
+        let c2 = Canvas::from_ppm(&c.to_ppm(PpmFormat::Binary)).unwrap();
+
+        for col in 0..3 {
+            for row in 0..2 {
+                assert_eq!(c2.get(col, row), c.get(col, row));
+            }
+        }
+    }
+
+    #[test]
+    fn reading_a_truncated_ppm() {
+        let ppm = b"P3\n2 1\n255\n255 255 255\n";
+
+        assert_eq!(
+            Canvas::from_ppm(ppm).err(),
+            Some(PpmError::UnexpectedEnd("sample"))
+        );
+    }
+
+    #[test]
+    fn reading_a_ppm_with_a_bad_header() {
+        let ppm = b"P3\ntwo 1\n255\n";
+
+        assert_eq!(
+            Canvas::from_ppm(ppm).err(),
+            Some(PpmError::BadNumber {
+                field: "width",
+                token: String::from("two")
+            })
+        );
+        assert_eq!(
+            Canvas::from_ppm(b"P3\n1 1\n0\n").err(),
+            Some(PpmError::BadMaxValue(0))
+        );
+    }
+
+    #[test]
+    fn reading_a_ppm_too_large_for_its_data() {
+        let ppm = b"P3\n4294967295 4294967295\n255\n0 0 0\n";
+
+        assert_eq!(
+            Canvas::from_ppm(ppm).err(),
+            Some(PpmError::BadSize {
+                width: 4294967295,
+                height: 4294967295
+            })
+        );
+        assert_eq!(
+            Canvas::from_ppm(b"P6\n2 2\n255\n\0\0\0").err(),
+            Some(PpmError::BadSize {
+                width: 2,
+                height: 2
+            })
+        );
+    }
+
+    #[test]
+    fn reading_a_ppm_with_a_sample_out_of_range() {
+        let ppm = b"P3\n1 1\n100\n100 101 0\n";
+
+        assert_eq!(
+            Canvas::from_ppm(ppm).err(),
+            Some(PpmError::SampleOutOfRange {
+                sample: 101,
+                max_value: 100
+            })
+        );
+    }
+}
@@ -4,11 +4,30 @@ use crate::geometry::{Matrix, Point, Vector};
 pub struct Ray {
     origin: Point,
     direction: Vector,
+    t_min: f64,
+    t_max: f64,
+    time: f64,
 }
 
 impl Ray {
+    /// Creates a ray whose interval is unbounded, so that every intersection
+    /// along the line is reported.
     pub fn new(origin: Point, direction: Vector) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            t_min: f64::NEG_INFINITY,
+            t_max: f64::INFINITY,
+            time: 0.0,
+        }
+    }
+
+    /// Restricts the ray to the half-open interval `[t_min, t_max)`. Shapes
+    /// only report intersections that fall inside the interval.
+    pub fn with_interval(mut self, t_min: f64, t_max: f64) -> Self {
+        self.t_min = t_min;
+        self.t_max = t_max;
+        self
     }
 
     pub fn position<T: Into<f64>>(&self, t: T) -> Point {
@@ -23,10 +42,38 @@ impl Ray {
         self.direction
     }
 
+    /// Sets the moment at which the ray samples the scene, for objects that
+    /// move while the camera's shutter is open.
+    pub fn with_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
+    }
+
+    pub fn t_min(&self) -> f64 {
+        self.t_min
+    }
+
+    pub fn t_max(&self) -> f64 {
+        self.t_max
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn contains(&self, t: f64) -> bool {
+        self.t_min <= t && t < self.t_max
+    }
+
+    // Since the direction isn't normalized, t values are the same in both
+    // spaces and the interval carries over unchanged.
     pub fn transform(&self, &transform: &Matrix) -> Ray {
         Ray {
             origin: transform * self.origin,
             direction: transform * self.direction,
+            t_min: self.t_min,
+            t_max: self.t_max,
+            time: self.time,
         }
     }
 }
@@ -49,6 +96,34 @@ mod tests {
         assert_eq!(r.direction, direction);
     }
 
+    #[test]
+    fn a_new_ray_has_an_unbounded_interval() {
+        let r = Ray::new(Point::new(1, 2, 3), Vector::new(4, 5, 6));
+
+        assert_eq!(r.t_min(), f64::NEG_INFINITY);
+        assert_eq!(r.t_max(), f64::INFINITY);
+        assert!(r.contains(-1000.0));
+        assert!(r.contains(1000.0));
+    }
+
+    #[test]
+    fn a_new_ray_samples_time_zero() {
+        let r = Ray::new(Point::new(1, 2, 3), Vector::new(4, 5, 6));
+
+        assert_eq!(r.time(), 0.0);
+        assert_eq!(r.with_time(0.75).time(), 0.75);
+    }
+
+    #[test]
+    fn a_ray_interval_is_half_open() {
+        let r = Ray::new(Point::new(1, 2, 3), Vector::new(4, 5, 6)).with_interval(0.0, 5.0);
+
+        assert!(r.contains(0.0));
+        assert!(r.contains(4.999));
+        assert!(!r.contains(5.0));
+        assert!(!r.contains(-0.001));
+    }
+
     #[test]
     fn computing_a_point_from_a_distance() {
         let r = Ray::new(Point::new(2, 3, 4), Vector::new(1, 0, 0));
@@ -80,4 +155,23 @@ mod tests {
         assert_eq!(r2.origin, Point::new(2, 6, 12));
         assert_eq!(r2.direction, Vector::new(0, 3, 0));
     }
+
+    #[test]
+    fn transforming_a_ray_preserves_its_interval() {
+        let r = Ray::new(Point::new(1, 2, 3), Vector::new(0, 1, 0)).with_interval(1.0, 2.0);
+        let m = Matrix::new().scale(2, 3, 4);
+
+        let r2 = r.transform(&m);
+
+        assert_eq!(r2.t_min(), 1.0);
+        assert_eq!(r2.t_max(), 2.0);
+    }
+
+    #[test]
+    fn transforming_a_ray_preserves_its_time() {
+        let r = Ray::new(Point::new(1, 2, 3), Vector::new(0, 1, 0)).with_time(0.5);
+        let m = Matrix::new().translate(3, 4, 5);
+
+        assert_eq!(r.transform(&m).time(), 0.5);
+    }
 }
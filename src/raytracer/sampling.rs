@@ -0,0 +1,195 @@
+use crate::geometry::{Tuple, Vector};
+
+/// A small, fast pseudo-random number generator (xorshift64*). Renders must
+/// be reproducible, so every generator is explicitly seeded; there is no
+/// global or time-based state.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // scramble the seed so that nearby seeds (e.g. neighbouring pixels)
+        // produce unrelated sequences, and so the state is never zero
+        Rng {
+            state: splitmix64(seed) | 1,
+        }
+    }
+
+    /// Returns a generator for the given pixel, so that each pixel gets its
+    /// own, repeatable sequence regardless of the order pixels are rendered.
+    pub fn for_pixel(px: usize, py: usize) -> Rng {
+        Rng::new(((py as u64) << 32) ^ (px as u64))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Returns a number uniformly distributed in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// -----------------------------------------------------------------------------
+
+/// Maps a point in the unit square to a point in the unit disk, using
+/// Shirley and Chiu's concentric mapping, which preserves stratification.
+pub fn concentric_disk(u: f64, v: f64) -> (f64, f64) {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, std::f64::consts::FRAC_PI_4 * (b / a))
+    } else {
+        (
+            b,
+            std::f64::consts::FRAC_PI_2 - std::f64::consts::FRAC_PI_4 * (a / b),
+        )
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+/// Returns a random direction in the hemisphere around `normal` (which must
+/// be normalized), with a probability density of `cos(theta) / PI`, where
+/// theta is the angle between the direction and the normal.
+pub fn cosine_hemisphere(normal: Vector, rng: &mut Rng) -> Vector {
+    // Malley's method: project uniform points on the disk up onto the
+    // hemisphere.
+    let (x, y) = concentric_disk(rng.next_f64(), rng.next_f64());
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    (tangent * x + bitangent * y + normal * z).norm()
+}
+
+/// Returns two unit vectors that, together with `normal`, form an
+/// orthonormal basis.
+pub fn orthonormal_basis(normal: Vector) -> (Vector, Vector) {
+    // Duff et al., "Building an Orthonormal Basis, Revisited"
+    let sign = 1.0_f64.copysign(normal.z());
+    let a = -1.0 / (sign + normal.z());
+    let b = normal.x() * normal.y() * a;
+    let tangent = Vector::new(
+        1.0 + sign * normal.x() * normal.x() * a,
+        sign * b,
+        -sign * normal.x(),
+    );
+    let bitangent = Vector::new(b, sign + normal.y() * normal.y() * a, -normal.y());
+    (tangent, bitangent)
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_seeded_generator_is_repeatable() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+
+        for _ in 0..10 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn different_pixels_get_different_sequences() {
+        let mut a = Rng::for_pixel(0, 0);
+        let mut b = Rng::for_pixel(1, 0);
+        let mut c = Rng::for_pixel(0, 1);
+
+        let (x, y, z) = (a.next_u64(), b.next_u64(), c.next_u64());
+
+        assert_ne!(x, y);
+        assert_ne!(x, z);
+        assert_ne!(y, z);
+    }
+
+    #[test]
+    fn uniform_numbers_are_in_the_unit_interval() {
+        let mut rng = Rng::new(7);
+        let mut sum = 0.0;
+
+        for _ in 0..10000 {
+            let x = rng.next_f64();
+            assert!((0.0..1.0).contains(&x));
+            sum += x;
+        }
+
+        assert!((sum / 10000.0 - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn concentric_disk_samples_lie_in_the_unit_disk() {
+        let mut rng = Rng::new(3);
+
+        for _ in 0..1000 {
+            let (x, y) = concentric_disk(rng.next_f64(), rng.next_f64());
+            assert!(x * x + y * y <= 1.0 + 1e-9);
+        }
+    }
+
+    #[test]
+    fn orthonormal_bases_are_orthonormal() {
+        let normals = [
+            Vector::new(0, 0, 1),
+            Vector::new(0, 0, -1),
+            Vector::new(1, 2, 3).norm(),
+            Vector::new(-3, 0.5, -0.2).norm(),
+        ];
+
+        for n in normals {
+            let (t, b) = orthonormal_basis(n);
+            assert!((t.mag() - 1.0).abs() < 1e-9);
+            assert!((b.mag() - 1.0).abs() < 1e-9);
+            assert!(t.dot(b).abs() < 1e-9);
+            assert!(t.dot(n).abs() < 1e-9);
+            assert!(b.dot(n).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn cosine_hemisphere_samples_are_cosine_weighted() {
+        let n = Vector::new(1, -1, 0.5).norm();
+        let mut rng = Rng::new(11);
+        let count = 20000;
+        let mut sum = 0.0;
+
+        for _ in 0..count {
+            let d = cosine_hemisphere(n, &mut rng);
+            assert!((d.mag() - 1.0).abs() < 1e-9);
+            assert!(d.dot(n) >= 0.0);
+            sum += d.dot(n);
+        }
+
+        // with a density of cos/PI, the mean of cos(theta) is 2/3
+        assert!((sum / count as f64 - 2.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn concentric_disk_maps_the_center_and_edges() {
+        assert_eq!(concentric_disk(0.5, 0.5), (0.0, 0.0));
+
+        let (x, y) = concentric_disk(1.0, 0.5);
+        assert!((x - 1.0).abs() < 1e-9 && y.abs() < 1e-9);
+
+        let (x, y) = concentric_disk(0.5, 0.0);
+        assert!(x.abs() < 1e-9 && (y + 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,281 @@
+use std::f64::consts::PI;
+
+use crate::geometry::{point, Tuple, Vector};
+use crate::raytracer::{color, Color, PointLight};
+
+/// Converts the model's luminance, in kcd/m^2, to the renderer's units.
+const SKY_SCALE: f64 = 0.05;
+
+/// The intensity of the sun overhead, before the atmosphere dims it.
+const SUN_INTENSITY: f64 = 3.0;
+
+/// How far away the sun light is placed. Point lights don't fall off with
+/// distance, so a distant one acts as a directional light.
+const SUN_DISTANCE: f64 = 1e6;
+
+/// The fraction of the sky's light reflected by the ground.
+const GROUND_ALBEDO: f64 = 0.3;
+
+/// Preetham, Shirley and Smits' analytic model of a clear daylight sky, for
+/// a sun in the given direction.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sky {
+    sun_direction: Vector,
+    turbidity: f64,
+}
+
+impl Sky {
+    /// Takes the direction towards the sun, with +y up, and the turbidity
+    /// of the atmosphere: about 2 for a very clear sky, 3 for a typical one,
+    /// and 10 for a hazy one. The model is only valid for turbidities from
+    /// 2 to 10, so it's clamped to that range.
+    pub fn new(sun_direction: Vector, turbidity: f64) -> Sky {
+        Sky {
+            sun_direction: sun_direction.norm(),
+            turbidity: turbidity.clamp(2.0, 10.0),
+        }
+    }
+
+    /// Returns a sky with the sun at the given elevation above the horizon,
+    /// and azimuth measured from -z towards +x, both in radians.
+    pub fn from_angles(elevation: f64, azimuth: f64, turbidity: f64) -> Sky {
+        let direction = Vector::new(
+            azimuth.sin() * elevation.cos(),
+            elevation.sin(),
+            -azimuth.cos() * elevation.cos(),
+        );
+        Sky::new(direction, turbidity)
+    }
+
+    pub fn sun_direction(&self) -> Vector {
+        self.sun_direction
+    }
+
+    pub fn turbidity(&self) -> f64 {
+        self.turbidity
+    }
+
+    // The sun's angle from the zenith. The model doesn't cover night, so
+    // the sun never sets below the horizon.
+    fn sun_theta(&self) -> f64 {
+        self.sun_direction.y().clamp(0.0, 1.0).acos()
+    }
+
+    /// Returns the light from the sky in the given direction. Below the
+    /// horizon, the ground reflects a little of the sky.
+    pub fn radiance(&self, direction: Vector) -> Color {
+        // below the horizon, show a dim reflection of the sky
+        let d = direction.norm();
+        let (d, ground) = if d.y() < 0.0 {
+            (Vector::new(d.x(), -d.y(), d.z()), GROUND_ALBEDO)
+        } else {
+            (d, 1.0)
+        };
+        let cos_theta = d.y().max(0.01);
+        let gamma = d.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+
+        let t = self.turbidity;
+        let theta_s = self.sun_theta();
+        let [cy, cx, cyy] = self.perez_coefficients();
+
+        let relative = |c: &[f64; 5]| perez(c, cos_theta, gamma) / perez(c, 1.0, theta_s);
+        let luminance = zenith_luminance(t, theta_s) * relative(&cy);
+        let x = zenith_chromaticity(&ZENITH_X, t, theta_s) * relative(&cx);
+        let y = zenith_chromaticity(&ZENITH_Y, t, theta_s) * relative(&cyy);
+
+        xyy_to_rgb(x, y, luminance * SKY_SCALE * ground)
+    }
+
+    /// Returns a light that matches the sun: far away in its direction, and
+    /// colored by the air it shines through. The sun reddens and dims
+    /// towards the horizon, and in hazier skies; it's black once it sets.
+    pub fn sun_light(&self) -> PointLight {
+        let position = point::ORIGIN + self.sun_direction * SUN_DISTANCE;
+        if self.sun_direction.y() <= 0.0 {
+            return PointLight::new(position, color::BLACK);
+        }
+
+        // the relative optical mass of the air the light passes through
+        let theta_deg = self.sun_theta().to_degrees();
+        let mass = 1.0 / (self.sun_theta().cos() + 0.15 * (93.885 - theta_deg).powf(-1.253));
+
+        // Rayleigh scattering by molecules, and Angstrom's formula for
+        // scattering by aerosols, at representative red, green and blue
+        // wavelengths (in micrometers)
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = |lambda: f64| {
+            let rayleigh = (-mass * 0.008735 * lambda.powf(-4.08)).exp();
+            let aerosol = (-mass * beta * lambda.powf(-1.3)).exp();
+            rayleigh * aerosol
+        };
+
+        let intensity = Color::new(
+            transmittance(0.68),
+            transmittance(0.55),
+            transmittance(0.44),
+        );
+        PointLight::new(position, intensity * SUN_INTENSITY)
+    }
+
+    // Perez's coefficients for luminance and the two chromaticities.
+    fn perez_coefficients(&self) -> [[f64; 5]; 3] {
+        let t = self.turbidity;
+        [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ]
+    }
+}
+
+const ZENITH_X: [[f64; 4]; 3] = [
+    [0.00166, -0.00375, 0.00209, 0.0],
+    [-0.02903, 0.06377, -0.03202, 0.00394],
+    [0.11693, -0.21196, 0.06052, 0.25886],
+];
+
+const ZENITH_Y: [[f64; 4]; 3] = [
+    [0.00275, -0.00610, 0.00317, 0.0],
+    [-0.04214, 0.08970, -0.04153, 0.00516],
+    [0.15346, -0.26756, 0.06670, 0.26688],
+];
+
+// Perez's sky distribution function, for a direction at `cos_theta` to the
+// zenith and `gamma` radians from the sun.
+fn perez(c: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    (1.0 + c[0] * (c[1] / cos_theta).exp())
+        * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos() * gamma.cos())
+}
+
+// The luminance straight up, in kcd/m^2.
+fn zenith_luminance(turbidity: f64, theta_s: f64) -> f64 {
+    let chi = (4.0 / 9.0 - turbidity / 120.0) * (PI - 2.0 * theta_s);
+    (4.0453 * turbidity - 4.9710) * chi.tan() - 0.2155 * turbidity + 2.4192
+}
+
+fn zenith_chromaticity(m: &[[f64; 4]; 3], turbidity: f64, theta_s: f64) -> f64 {
+    let t = [turbidity * turbidity, turbidity, 1.0];
+    let th = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.0];
+    (0..3)
+        .map(|i| t[i] * (0..4).map(|j| m[i][j] * th[j]).sum::<f64>())
+        .sum()
+}
+
+// Converts CIE xyY to linear sRGB, clipping colors outside its gamut.
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color {
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+
+    let r = 3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z;
+    let g = -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z;
+    let b = 0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z;
+    Color::new(r.max(0.0), g.max(0.0), b.max(0.0))
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_4;
+
+    fn luminance(c: Color) -> f64 {
+        0.2126 * c.red() + 0.7152 * c.green() + 0.0722 * c.blue()
+    }
+
+    #[test]
+    fn the_zenith_has_the_model_luminance() {
+        let sky = Sky::from_angles(FRAC_PI_4, 0.0, 3.0);
+
+        let zenith = sky.radiance(Vector::new(0, 1, 0));
+
+        // (4.0453 * 3 - 4.9710) * tan(chi) - 0.2155 * 3 + 2.4192, scaled
+        assert!((luminance(zenith) - 7.3207 * SKY_SCALE).abs() < 0.001);
+    }
+
+    #[test]
+    fn a_clear_sky_is_blue() {
+        let sky = Sky::from_angles(FRAC_PI_4, 0.0, 2.5);
+
+        let zenith = sky.radiance(Vector::new(0, 1, 0));
+
+        assert!(zenith.blue() > zenith.green());
+        assert!(zenith.green() > zenith.red());
+    }
+
+    #[test]
+    fn the_sky_is_brightest_around_the_sun() {
+        let sky = Sky::from_angles(0.3, 0.0, 3.0);
+
+        let towards = luminance(sky.radiance(Vector::new(0, 0.3, -1)));
+        let away = luminance(sky.radiance(Vector::new(0, 0.3, 1)));
+        let beside = luminance(sky.radiance(Vector::new(1, 0.3, 0)));
+
+        assert!(towards > beside);
+        assert!(beside > away);
+    }
+
+    #[test]
+    fn the_ground_reflects_the_sky() {
+        let sky = Sky::from_angles(FRAC_PI_4, 0.0, 3.0);
+
+        let above = sky.radiance(Vector::new(1, 1, 0));
+        let ground = sky.radiance(Vector::new(1, -1, 0));
+
+        assert_eq!(ground, above * GROUND_ALBEDO);
+    }
+
+    #[test]
+    fn the_sun_light_is_far_away_towards_the_sun() {
+        let sky = Sky::new(Vector::new(1, 1, 0), 3.0);
+
+        let sun = sky.sun_light();
+
+        let direction = (sun.position() - point::ORIGIN).norm();
+        assert_eq!(direction, Vector::new(1, 1, 0).norm());
+        assert!((sun.position() - point::ORIGIN).mag() > 1e5);
+    }
+
+    #[test]
+    fn the_sun_reddens_and_dims_towards_the_horizon() {
+        let high = Sky::from_angles(1.2, 0.0, 3.0).sun_light().intensity();
+        let low = Sky::from_angles(0.05, 0.0, 3.0).sun_light().intensity();
+
+        assert!(low.red() < high.red());
+        assert!(low.red() / low.blue() > high.red() / high.blue());
+        assert!(high.red() > high.blue());
+    }
+
+    #[test]
+    fn haze_dims_the_sun() {
+        let clear = Sky::from_angles(0.5, 0.0, 2.0).sun_light().intensity();
+        let hazy = Sky::from_angles(0.5, 0.0, 8.0).sun_light().intensity();
+
+        assert!(hazy.green() < clear.green());
+    }
+
+    #[test]
+    fn a_set_sun_gives_no_light() {
+        let sky = Sky::from_angles(-0.1, 0.0, 3.0);
+
+        assert_eq!(sky.sun_light().intensity(), color::BLACK);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::geometry::{view_transform, Point, Vector};
+use crate::raytracer::{Camera, Canvas, World};
+
+/// How the two eyes of a stereo rig are aimed so that their views meet at
+/// the convergence distance.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Convergence {
+    /// Each eye is rotated to look at the convergence point. Simple, but it
+    /// introduces vertical parallax toward the edges of the image.
+    ToeIn,
+    /// The eyes look in parallel, and each canvas is shifted sideways so
+    /// that the views coincide at the convergence distance. This avoids
+    /// vertical parallax, and is the usual choice for VR.
+    OffAxis,
+}
+
+/// How a stereo pair is packed into a single image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StereoPacking {
+    /// The left eye's image on the left, the right eye's on the right.
+    SideBySide,
+    /// The left eye's image above the right eye's.
+    TopBottom,
+}
+
+/// A pair of cameras, one for each eye, placed either side of a viewpoint.
+#[derive(Debug, Clone)]
+pub struct StereoCamera {
+    camera: Camera,
+    from: Point,
+    to: Point,
+    up: Vector,
+    interocular: f64,
+    convergence: Convergence,
+    convergence_distance: f64,
+}
+
+impl StereoCamera {
+    /// Creates a rig looking from `from` toward `to`. Both eyes use the
+    /// given camera's resolution, projection and sampling settings; its
+    /// transform is replaced. The eyes are 64mm apart by default (assuming
+    /// a world unit of one meter), and converge at `to`.
+    pub fn new(camera: Camera, from: Point, to: Point, up: Vector) -> StereoCamera {
+        StereoCamera {
+            camera,
+            from,
+            to,
+            up,
+            interocular: 0.064,
+            convergence: Convergence::OffAxis,
+            convergence_distance: (to - from).mag(),
+        }
+    }
+
+    pub fn with_interocular(mut self, interocular: f64) -> Self {
+        self.interocular = interocular;
+        self
+    }
+
+    pub fn with_convergence(mut self, convergence: Convergence) -> Self {
+        self.convergence = convergence;
+        self
+    }
+
+    /// Sets the distance in front of the rig at which the two views
+    /// coincide; objects at this distance appear at the depth of the screen.
+    pub fn with_convergence_distance(mut self, distance: f64) -> Self {
+        self.convergence_distance = distance;
+        self
+    }
+
+    pub fn left_camera(&self) -> Camera {
+        self.eye_camera(1.0)
+    }
+
+    pub fn right_camera(&self) -> Camera {
+        self.eye_camera(-1.0)
+    }
+
+    // `side` is 1 for the left eye and -1 for the right.
+    fn eye_camera(&self, side: f64) -> Camera {
+        let forward = (self.to - self.from).norm();
+        let left = forward.cross(self.up.norm());
+        let half = self.interocular / 2.0;
+        let eye = self.from + left * (side * half);
+
+        match self.convergence {
+            Convergence::ToeIn => {
+                let target = self.from + forward * self.convergence_distance;
+                self.camera
+                    .clone()
+                    .with_transform(view_transform(eye, target, self.up))
+            }
+            Convergence::OffAxis => {
+                // in the eye's own space (where +x is to the left), the point
+                // straight ahead of the rig lies `half` to the side, so shift
+                // the canvas to center it on that point at the convergence
+                // distance.
+                let shift = -side * half / self.convergence_distance;
+                self.camera
+                    .clone()
+                    .with_transform(view_transform(eye, eye + forward, self.up))
+                    .with_lens_shift(shift, 0.0)
+            }
+        }
+    }
+
+    /// Renders the left and right eyes' views, in that order.
+    pub fn render(&self, world: &World) -> (Canvas, Canvas) {
+        (
+            self.left_camera().render(world),
+            self.right_camera().render(world),
+        )
+    }
+
+    pub fn render_packed(&self, world: &World, packing: StereoPacking) -> Canvas {
+        let (left, right) = self.render(world);
+        pack(&left, &right, packing)
+    }
+}
+
+fn pack(left: &Canvas, right: &Canvas, packing: StereoPacking) -> Canvas {
+    let (width, height) = (left.width(), left.height());
+    let (mut image, offset) = match packing {
+        StereoPacking::SideBySide => (Canvas::new(width * 2, height), (width, 0)),
+        StereoPacking::TopBottom => (Canvas::new(width, height * 2), (0, height)),
+    };
+
+    for col in 0..width {
+        for row in 0..height {
+            image.set(col, row, left.get(col, row));
+            image.set(col + offset.0, row + offset.1, right.get(col, row));
+        }
+    }
+    image
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::Tuple,
+        raytracer::{color, world::test_utils::default_world, Color},
+    };
+    use std::f64::consts::PI;
+
+    fn rig(convergence: Convergence) -> StereoCamera {
+        StereoCamera::new(
+            Camera::new(101, 101, PI / 2.0),
+            Point::new(0, 0, -5),
+            Point::new(0, 0, 0),
+            Vector::new(0, 1, 0),
+        )
+        .with_interocular(0.5)
+        .with_convergence(convergence)
+    }
+
+    #[test]
+    fn the_eyes_are_either_side_of_the_viewpoint() {
+        let rig = rig(Convergence::OffAxis);
+
+        let left = rig.left_camera().ray_for_pixel(50, 50);
+        let right = rig.right_camera().ray_for_pixel(50, 50);
+
+        // looking toward +z, the viewer's left is -x
+        assert_eq!(left.origin(), Point::new(-0.25, 0, -5));
+        assert_eq!(right.origin(), Point::new(0.25, 0, -5));
+    }
+
+    #[test]
+    fn toe_in_eyes_look_at_the_convergence_point() {
+        let rig = rig(Convergence::ToeIn);
+
+        let left = rig.left_camera().ray_for_pixel(50, 50);
+        let right = rig.right_camera().ray_for_pixel(50, 50);
+
+        assert_eq!(left.direction(), Vector::new(0.25, 0, 5).norm());
+        assert_eq!(right.direction(), Vector::new(-0.25, 0, 5).norm());
+    }
+
+    #[test]
+    fn off_axis_eyes_look_in_parallel_but_converge_at_the_same_distance() {
+        let rig = rig(Convergence::OffAxis).with_convergence_distance(10.0);
+
+        let left = rig.left_camera();
+        let right = rig.right_camera();
+
+        // the cameras aren't turned, but the shifted canvases make the
+        // center rays cross at the convergence distance
+        let l = left.ray_for_pixel(50, 50);
+        let r = right.ray_for_pixel(50, 50);
+        assert_eq!(l.direction(), Vector::new(0.25, 0, 10).norm());
+        assert_eq!(r.direction(), Vector::new(-0.25, 0, 10).norm());
+        assert_eq!(
+            l.position(10.0 / l.direction().z()),
+            r.position(10.0 / r.direction().z())
+        );
+    }
+
+    #[test]
+    fn off_axis_eyes_have_no_vertical_parallax() {
+        let rig = rig(Convergence::OffAxis);
+
+        let l = rig.left_camera().ray_for_pixel(0, 0);
+        let r = rig.right_camera().ray_for_pixel(0, 0);
+
+        // at the same depth, the corner rays reach the same height
+        let l = l.position(5.0 / l.direction().z());
+        let r = r.position(5.0 / r.direction().z());
+        assert!((l.y() - r.y()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rendering_a_stereo_pair() {
+        let w = default_world();
+        let rig = StereoCamera::new(
+            Camera::new(11, 11, PI / 2.0),
+            Point::new(0, 0, -5),
+            Point::new(0, 0, 0),
+            Vector::new(0, 1, 0),
+        )
+        .with_interocular(0.0);
+
+        let (left, right) = rig.render(&w);
+
+        assert_eq!(left.get(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+        assert_eq!(right.get(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn packing_a_stereo_pair_side_by_side() {
+        let mut left = Canvas::new(2, 1);
+        let mut right = Canvas::new(2, 1);
+        left.set(1, 0, color::WHITE);
+        right.set(0, 0, Color::new(1, 0, 0));
+
+        let image = pack(&left, &right, StereoPacking::SideBySide);
+
+        assert_eq!((image.width(), image.height()), (4, 1));
+        assert_eq!(image.get(1, 0), color::WHITE);
+        assert_eq!(image.get(2, 0), Color::new(1, 0, 0));
+    }
+
+    #[test]
+    fn packing_a_stereo_pair_top_to_bottom() {
+        let mut left = Canvas::new(2, 1);
+        let mut right = Canvas::new(2, 1);
+        left.set(1, 0, color::WHITE);
+        right.set(0, 0, Color::new(1, 0, 0));
+
+        let image = pack(&left, &right, StereoPacking::TopBottom);
+
+        assert_eq!((image.width(), image.height()), (2, 2));
+        assert_eq!(image.get(1, 0), color::WHITE);
+        assert_eq!(image.get(0, 1), Color::new(1, 0, 0));
+    }
+}
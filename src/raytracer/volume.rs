@@ -0,0 +1,128 @@
+use crate::raytracer::{color, Color};
+
+/// Exponential distance fog: the farther away a surface is, the more of its
+/// color is replaced by the fog's.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fog {
+    color: Color,
+    density: f64,
+}
+
+impl Fog {
+    /// Takes the fog's color, and its density: the fraction of light lost
+    /// per unit of distance.
+    pub fn new(color: Color, density: f64) -> Fog {
+        Fog { color, density }
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    pub fn density(&self) -> f64 {
+        self.density
+    }
+
+    /// Returns the fraction of light that makes it through `distance` units
+    /// of fog.
+    pub fn transmittance(&self, distance: f64) -> f64 {
+        (-self.density * distance).exp()
+    }
+
+    /// Blends a color seen `distance` units away into the fog.
+    pub fn apply(&self, color: Color, distance: f64) -> Color {
+        let transmittance = self.transmittance(distance);
+        color * transmittance + self.color * (1.0 - transmittance)
+    }
+}
+
+/// A homogeneous participating medium, such as smoke, murky water or
+/// colored glass, that fills a closed shape. Light passing through it is
+/// absorbed, and scattered equally in all directions. The coefficients are
+/// per unit of distance, for each color channel.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Medium {
+    absorption: Color,
+    scattering: Color,
+}
+
+impl Medium {
+    pub fn new(absorption: Color, scattering: Color) -> Medium {
+        Medium {
+            absorption,
+            scattering,
+        }
+    }
+
+    pub fn absorption(&self) -> Color {
+        self.absorption
+    }
+
+    pub fn scattering(&self) -> Color {
+        self.scattering
+    }
+
+    /// Returns the total fraction of light lost per unit of distance, to
+    /// absorption and to scattering.
+    pub fn extinction(&self) -> Color {
+        self.absorption + self.scattering
+    }
+
+    /// Returns the fraction of light in each channel that makes it through
+    /// `distance` units of the medium.
+    pub fn transmittance(&self, distance: f64) -> Color {
+        let extinction = self.extinction();
+        Color::new(
+            (-extinction.red() * distance).exp(),
+            (-extinction.green() * distance).exp(),
+            (-extinction.blue() * distance).exp(),
+        )
+    }
+}
+
+impl Default for Medium {
+    fn default() -> Self {
+        Medium::new(color::BLACK, color::BLACK)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fog_fades_colors_with_distance() {
+        let fog = Fog::new(Color::new(0.5, 0.5, 0.5), 0.5);
+
+        assert_eq!(fog.apply(Color::new(1, 0, 0), 0.0), Color::new(1, 0, 0));
+        assert_eq!(
+            fog.apply(Color::new(1, 0, 0), 2.0),
+            Color::new(
+                0.5 + 0.5 * (-1.0_f64).exp(),
+                0.5 - 0.5 * (-1.0_f64).exp(),
+                0.5 - 0.5 * (-1.0_f64).exp()
+            )
+        );
+        assert_eq!(
+            fog.apply(Color::new(1, 0, 0), 1000.0),
+            Color::new(0.5, 0.5, 0.5)
+        );
+    }
+
+    #[test]
+    fn a_medium_absorbs_and_scatters_light() {
+        let m = Medium::new(Color::new(0.1, 0.2, 0.3), Color::new(0.4, 0.3, 0.2));
+
+        assert_eq!(m.extinction(), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(m.transmittance(2.0), Color::new(0.36788, 0.36788, 0.36788));
+    }
+
+    #[test]
+    fn an_empty_medium_is_transparent() {
+        let m = Medium::default();
+
+        assert_eq!(m.transmittance(100.0), color::WHITE);
+    }
+}
@@ -1,75 +1,411 @@
-use std::marker::PhantomData;
-
-use crate::geometry::Point;
-use crate::raytracer::{color, Color, IntersectionState, Intersections, PointLight, Ray};
+use crate::geometry::{Point, Vector};
+use crate::raytracer::{
+    color, AmbientOcclusion, Color, Environment, Fog, Intersection, IntersectionState,
+    Intersections, Medium, PointLight, Ray,
+};
 use crate::shape::Shape;
+use crate::EPSILON;
+
+/// The number of points at which light scattered within a volume is sampled
+/// along each ray.
+const VOLUME_STEPS: usize = 16;
+
+/// A stable handle to an object in a `World`. Handles stay valid while other
+/// objects are added or removed, and are never reused within a world.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(u64);
+
+/// A stable handle to a light in a `World`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LightId(u64);
 
 #[derive(Debug)]
-pub struct World<'a> {
+pub struct World {
     objects: Vec<Shape>,
+    object_ids: Vec<ObjectId>,
     lights: Vec<PointLight>,
-    _marker: PhantomData<&'a Shape>,
+    light_ids: Vec<LightId>,
+    next_id: u64,
+    ambient_occlusion: Option<AmbientOcclusion>,
+    environment: Environment,
+    fog: Option<Fog>,
 }
 
-impl<'a> World<'a> {
-    pub fn new() -> World<'a> {
+impl World {
+    pub fn new() -> World {
         World {
             objects: vec![],
+            object_ids: vec![],
             lights: vec![],
-            _marker: PhantomData,
+            light_ids: vec![],
+            next_id: 0,
+            ambient_occlusion: None,
+            environment: Environment::default(),
+            fog: None,
         }
     }
 
-    pub fn with_lights(mut self, mut lights: Vec<PointLight>) -> Self {
-        self.lights.append(&mut lights);
+    pub fn with_lights(mut self, lights: Vec<PointLight>) -> Self {
+        for light in lights {
+            self.add_light(light);
+        }
+        self
+    }
+
+    pub fn with_objects(mut self, objects: Vec<Shape>) -> Self {
+        for object in objects {
+            self.add_object(object);
+        }
+        self
+    }
+
+    /// Darkens the ambient light reaching each point by how much of the sky
+    /// above it is hidden by nearby objects.
+    pub fn with_ambient_occlusion(mut self, ambient_occlusion: AmbientOcclusion) -> Self {
+        self.ambient_occlusion = Some(ambient_occlusion);
+        self
+    }
+
+    pub fn ambient_occlusion(&self) -> Option<AmbientOcclusion> {
+        self.ambient_occlusion
+    }
+
+    /// Sets the light arriving from beyond the objects in the world, seen by
+    /// rays that miss them all.
+    pub fn with_environment(mut self, environment: Environment) -> Self {
+        self.environment = environment;
         self
     }
 
-    pub fn with_objects(mut self, mut objects: Vec<Shape>) -> Self {
-        self.objects.append(&mut objects);
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    /// Fades surfaces into the fog with their distance from the eye. Rays
+    /// that miss everything still see the environment, so a sky stays
+    /// visible.
+    pub fn with_fog(mut self, fog: Fog) -> Self {
+        self.fog = Some(fog);
         self
     }
 
-    pub fn intersect(&'a self, r: Ray) -> Intersections<'a> {
+    pub fn fog(&self) -> Option<Fog> {
+        self.fog
+    }
+
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    // -------------------------------------------------------------------------
+
+    pub fn add_object(&mut self, object: Shape) -> ObjectId {
+        let id = ObjectId(self.next_id());
+        self.objects.push(object);
+        self.object_ids.push(id);
+        id
+    }
+
+    pub fn remove_object(&mut self, id: ObjectId) -> Option<Shape> {
+        let index = self.object_index(id)?;
+        self.object_ids.remove(index);
+        Some(self.objects.remove(index))
+    }
+
+    /// Replaces the object with the given handle, returning the old one. The
+    /// handle continues to refer to the new object.
+    pub fn replace_object(&mut self, id: ObjectId, object: Shape) -> Option<Shape> {
+        let slot = self.object_mut(id)?;
+        Some(std::mem::replace(slot, object))
+    }
+
+    pub fn object(&self, id: ObjectId) -> Option<&Shape> {
+        self.object_index(id).map(|index| &self.objects[index])
+    }
+
+    pub fn object_mut(&mut self, id: ObjectId) -> Option<&mut Shape> {
+        self.object_index(id).map(|index| &mut self.objects[index])
+    }
+
+    /// Iterates over the objects in the order they were added.
+    pub fn objects(&self) -> impl Iterator<Item = (ObjectId, &Shape)> {
+        self.object_ids.iter().copied().zip(self.objects.iter())
+    }
+
+    pub fn objects_mut(&mut self) -> impl Iterator<Item = (ObjectId, &mut Shape)> {
+        self.object_ids.iter().copied().zip(self.objects.iter_mut())
+    }
+
+    /// Returns the handle of the given object, if it belongs to this world.
+    /// Objects are compared by address, not by value.
+    pub fn id_of(&self, object: &Shape) -> Option<ObjectId> {
+        let index = self.objects.iter().position(|o| std::ptr::eq(o, object))?;
+        Some(self.object_ids[index])
+    }
+
+    fn object_index(&self, id: ObjectId) -> Option<usize> {
+        self.object_ids.iter().position(|&it| it == id)
+    }
+
+    // -------------------------------------------------------------------------
+
+    pub fn add_light(&mut self, light: PointLight) -> LightId {
+        let id = LightId(self.next_id());
+        self.lights.push(light);
+        self.light_ids.push(id);
+        id
+    }
+
+    pub fn remove_light(&mut self, id: LightId) -> Option<PointLight> {
+        let index = self.light_index(id)?;
+        self.light_ids.remove(index);
+        Some(self.lights.remove(index))
+    }
+
+    pub fn light(&self, id: LightId) -> Option<&PointLight> {
+        self.light_index(id).map(|index| &self.lights[index])
+    }
+
+    pub fn light_mut(&mut self, id: LightId) -> Option<&mut PointLight> {
+        self.light_index(id).map(|index| &mut self.lights[index])
+    }
+
+    /// Iterates over the lights in the order they were added.
+    pub fn lights(&self) -> impl Iterator<Item = (LightId, &PointLight)> {
+        self.light_ids.iter().copied().zip(self.lights.iter())
+    }
+
+    pub fn lights_mut(&mut self) -> impl Iterator<Item = (LightId, &mut PointLight)> {
+        self.light_ids.iter().copied().zip(self.lights.iter_mut())
+    }
+
+    fn light_index(&self, id: LightId) -> Option<usize> {
+        self.light_ids.iter().position(|&it| it == id)
+    }
+
+    // -------------------------------------------------------------------------
+
+    pub fn intersect(&self, r: Ray) -> Intersections<'_> {
         let xs: Intersections = self.objects.iter().flat_map(|o| o.intersect(r)).collect();
         xs.sort()
     }
 
-    pub fn shade_hit(&self, state: IntersectionState) -> Color {
-        self.lights
+    /// Returns true as soon as any object intersects the ray within the
+    /// ray's interval. Unlike `intersect`, this neither collects nor sorts
+    /// the intersections. Volumes don't occlude anything; light passing
+    /// through them is dimmed instead (see `light_transmittance`).
+    pub fn is_occluded(&self, r: Ray) -> bool {
+        self.objects
+            .iter()
+            .filter(|o| o.material().medium().is_none())
+            .any(|o| o.intersects(r))
+    }
+
+    pub(crate) fn point_lights(&self) -> &[PointLight] {
+        &self.lights
+    }
+
+    /// Returns the objects that give off light of their own.
+    pub(crate) fn emitters(&self) -> impl Iterator<Item = &Shape> {
+        self.objects
             .iter()
-            .map(|light| {
-                let in_shadow = self.is_shadowed(state.over_point(), *light);
-                state.lighting(*light, in_shadow)
-            })
-            .fold(color::BLACK, |acc, c| acc + c)
+            .filter(|o| o.material().emission() != color::BLACK)
+    }
+
+    pub fn shade_hit(&self, state: IntersectionState) -> Color {
+        self.light_contributions(&state)
+            .fold(state.object().material().emission(), |acc, (_, c)| acc + c)
+    }
+
+    /// The light each of the world's lights contributes at a hit, including
+    /// its share of the ambient light. Together with the material's
+    /// emission, these make up the shaded color.
+    pub(crate) fn light_contributions<'a>(
+        &'a self,
+        state: &'a IntersectionState,
+    ) -> impl Iterator<Item = (LightId, Color)> + 'a {
+        let material = state.object().material();
+        let occluded = match self.ambient_occlusion {
+            Some(ao) => {
+                1.0 - ao.unoccluded_at(self, state.over_point(), state.normal_v(), state.time())
+            }
+            None => 0.0,
+        };
+
+        self.lights().map(move |(id, light)| {
+            let in_shadow = self.is_shadowed(state.over_point(), *light, state.time());
+            let transmittance =
+                self.light_transmittance(state.over_point(), light.position(), state.time());
+            let light = PointLight::new(light.position(), light.intensity() * transmittance);
+            let color = state.lighting(light, in_shadow) - material.ambient_light(light) * occluded;
+            (id, color)
+        })
     }
 
     pub fn color_at(&self, ray: Ray) -> Color {
         let xs = self.intersect(ray);
-        if let Some(x) = xs.hit() {
-            self.shade_hit(x.compute_state(ray))
+        match xs.hit() {
+            Some(x) => {
+                let color = match x.object().material().medium() {
+                    Some(medium) => {
+                        let segment = self.volume_segment(ray, x, medium);
+                        self.color_at(segment.rest) * segment.transmittance + segment.scattered
+                    }
+                    None => self.shade_hit(x.compute_state(ray)),
+                };
+                self.fogged(color, ray, x.t())
+            }
+            None => self.environment.radiance(ray.direction()),
+        }
+    }
+
+    /// Fades a color seen at `t` along the ray into the world's fog.
+    pub(crate) fn fogged(&self, color: Color, ray: Ray, t: f64) -> Color {
+        match self.fog {
+            Some(fog) => fog.apply(color, self.distance_to(ray, t)),
+            None => color,
+        }
+    }
+
+    // The distance from the start of the ray's interval to `t` along it.
+    pub(crate) fn distance_to(&self, ray: Ray, t: f64) -> f64 {
+        (t - ray.t_min().max(0.0)) * ray.direction().mag()
+    }
+
+    /// Follows a ray through the volume it hits, up to the volume's far
+    /// side, or the first object inside it.
+    pub(crate) fn volume_segment(
+        &self,
+        ray: Ray,
+        hit: &Intersection,
+        medium: Medium,
+    ) -> VolumeSegment {
+        let volume = hit.object();
+
+        // the ray either enters the volume at the hit, or starts inside it
+        let exit = volume
+            .intersect(Ray::new(ray.origin(), ray.direction()).with_time(ray.time()))
+            .iter()
+            .map(|x| x.t())
+            .fold(f64::NEG_INFINITY, f64::max);
+        let start = if hit.t() < exit {
+            hit.t()
         } else {
-            color::BLACK
+            ray.t_min().max(0.0)
+        };
+
+        let inside = ray.with_interval(start, exit);
+        let (end, rest) = match self
+            .intersect(inside)
+            .iter()
+            .find(|x| x.t() > start && !std::ptr::eq(x.object(), volume))
+        {
+            Some(x) => (x.t(), ray.with_interval(x.t(), ray.t_max())),
+            None => (exit, ray.with_interval(exit + EPSILON, ray.t_max())),
+        };
+
+        // Single scattering: march along the segment, adding the light
+        // scattered towards the eye at each step. The phase function is
+        // isotropic, 1 / (4 PI), and point lights are scaled by PI as for
+        // surfaces.
+        let speed = ray.direction().mag();
+        let step = (end - start) / VOLUME_STEPS as f64;
+        let mut scattered = color::BLACK;
+        for i in 0..VOLUME_STEPS {
+            let t = start + (i as f64 + 0.5) * step;
+            let point = ray.position(t);
+            let to_eye = medium.transmittance((t - start) * speed);
+
+            for light in &self.lights {
+                if self.is_shadowed(point, *light, ray.time()) {
+                    continue;
+                }
+                let to_light = self.light_transmittance(point, light.position(), ray.time());
+                scattered = scattered
+                    + to_eye
+                        * to_light
+                        * medium.scattering()
+                        * light.intensity()
+                        * (step * speed / 4.0);
+            }
+        }
+
+        VolumeSegment {
+            scattered,
+            transmittance: medium.transmittance((end - start) * speed),
+            rest,
+        }
+    }
+
+    /// Returns the fraction of the light traveling from `from` to `to` that
+    /// makes it through the volumes in between.
+    pub(crate) fn light_transmittance(&self, from: Point, to: Point, time: f64) -> Color {
+        let v = to - from;
+        self.transmittance(from, v.norm(), v.mag(), time)
+    }
+
+    /// Returns the fraction of the environment's light arriving from the
+    /// given direction that makes it through the volumes to `from`.
+    pub(crate) fn environment_transmittance(
+        &self,
+        from: Point,
+        direction: Vector,
+        time: f64,
+    ) -> Color {
+        self.transmittance(from, direction.norm(), f64::INFINITY, time)
+    }
+
+    fn transmittance(&self, from: Point, direction: Vector, distance: f64, time: f64) -> Color {
+        let mut transmittance = color::WHITE;
+        for volume in &self.objects {
+            let medium = match volume.material().medium() {
+                Some(medium) => medium,
+                None => continue,
+            };
+
+            // the boundary crossings along the line pair up into spans
+            // within the volume
+            let xs = volume.intersect(Ray::new(from, direction).with_time(time));
+            let ts: Vec<f64> = xs.iter().map(|x| x.t()).collect();
+            let inside: f64 = ts
+                .chunks(2)
+                .filter(|span| span.len() == 2)
+                .map(|span| (span[1].min(distance) - span[0].max(0.0)).max(0.0))
+                .sum();
+
+            if inside > 0.0 {
+                transmittance = transmittance * medium.transmittance(inside);
+            }
         }
+        transmittance
     }
 
-    fn is_shadowed(&self, point: Point, light: PointLight) -> bool {
+    pub(crate) fn is_shadowed(&self, point: Point, light: PointLight, time: f64) -> bool {
         let v = light.position() - point;
         let distance = v.mag();
         let direction = v.norm();
 
-        let r = Ray::new(point, direction);
-        let xs = self.intersect(r);
-
-        match xs.hit() {
-            Some(hit) => hit.t() < distance,
-            None => false,
-        }
+        let r = Ray::new(point, direction)
+            .with_interval(0.0, distance)
+            .with_time(time);
+        self.is_occluded(r)
     }
 }
 
-impl<'a> Default for World<'a> {
+/// The part of a ray that passes through a volume.
+pub(crate) struct VolumeSegment {
+    /// The light scattered towards the ray's origin from within the volume.
+    pub(crate) scattered: Color,
+    /// The fraction of the light from beyond the segment that makes it
+    /// through the volume.
+    pub(crate) transmittance: Color,
+    /// The rest of the ray, beyond the segment.
+    pub(crate) rest: Ray,
+}
+
+impl Default for World {
     fn default() -> Self {
         Self::new()
     }
@@ -84,7 +420,7 @@ pub mod test_utils {
         shape::Sphere,
     };
 
-    pub fn default_world<'a>() -> World<'a> {
+    pub fn default_world() -> World {
         let light = PointLight::new(Point::new(-10, 10, -10), Color::new(1, 1, 1));
         let material = Material::new()
             .with_color(Color::new(0.8, 1.0, 0.6))
@@ -94,11 +430,9 @@ pub mod test_utils {
         let s1 = Sphere::new().with_material(material);
         let s2 = Sphere::new().with_transform(Matrix::new().scale_u(0.5));
 
-        let mut w = World::new();
-        w.objects = vec![s1, s2];
-        w.lights = vec![light];
-
-        w
+        World::new()
+            .with_lights(vec![light])
+            .with_objects(vec![s1, s2])
     }
 }
 
@@ -112,6 +446,12 @@ mod tests {
         shape::Sphere,
     };
 
+    #[test]
+    fn a_world_can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<World>();
+    }
+
     #[test]
     fn creating_a_world() {
         let w = World::new();
@@ -153,6 +493,97 @@ mod tests {
         assert_eq!(xs[3].t(), 6.0);
     }
 
+    #[test]
+    fn adding_objects_returns_distinct_handles() {
+        let mut w = World::new();
+
+        let a = w.add_object(Sphere::new());
+        let b = w.add_object(Sphere::new());
+
+        assert_ne!(a, b);
+        assert_eq!(w.objects.len(), 2);
+        assert_eq!(
+            w.objects().map(|(id, _)| id).collect::<Vec<_>>(),
+            vec![a, b]
+        );
+    }
+
+    #[test]
+    fn removing_an_object_keeps_other_handles_valid() {
+        let mut w = World::new();
+        let s1 = Sphere::new().with_transform(Matrix::new().translate(1, 0, 0));
+        let s2 = Sphere::new().with_transform(Matrix::new().translate(2, 0, 0));
+        let a = w.add_object(s1.clone());
+        let b = w.add_object(s2.clone());
+
+        assert_eq!(w.remove_object(a), Some(s1));
+        assert_eq!(w.remove_object(a), None);
+        assert_eq!(w.object(a), None);
+        assert_eq!(w.object(b), Some(&s2));
+    }
+
+    #[test]
+    fn handles_are_not_reused_after_removal() {
+        let mut w = World::new();
+        let a = w.add_object(Sphere::new());
+        w.remove_object(a);
+
+        let b = w.add_object(Sphere::new());
+
+        assert_ne!(a, b);
+        assert_eq!(w.object(a), None);
+    }
+
+    #[test]
+    fn editing_an_object_in_place() {
+        let mut w = World::new();
+        let id = w.add_object(Sphere::new());
+        let m = Material::new().with_ambient(1.0);
+
+        let object = w.object_mut(id).unwrap();
+        *object = object.clone().with_material(m);
+
+        assert_eq!(w.object(id).unwrap().material(), m);
+    }
+
+    #[test]
+    fn replacing_an_object() {
+        let mut w = World::new();
+        let id = w.add_object(Sphere::new());
+        let s = Sphere::new().with_transform(Matrix::new().scale_u(2));
+
+        let old = w.replace_object(id, s.clone());
+
+        assert_eq!(old, Some(Sphere::new()));
+        assert_eq!(w.object(id), Some(&s));
+    }
+
+    #[test]
+    fn finding_the_handle_of_an_object() {
+        let w = default_world();
+        let ids: Vec<ObjectId> = w.objects().map(|(id, _)| id).collect();
+        let other = w.objects[1].clone();
+
+        assert_eq!(w.id_of(&w.objects[0]), Some(ids[0]));
+        assert_eq!(w.id_of(&w.objects[1]), Some(ids[1]));
+        assert_eq!(w.id_of(&other), None);
+    }
+
+    #[test]
+    fn adding_editing_and_removing_lights() {
+        let mut w = World::new();
+        let l1 = PointLight::new(Point::new(0, 10, 0), color::WHITE);
+        let l2 = PointLight::new(Point::new(0, -10, 0), color::WHITE);
+        let a = w.add_light(l1);
+        let b = w.add_light(l2);
+
+        *w.light_mut(b).unwrap() = l1;
+        assert_eq!(w.light(b), Some(&l1));
+
+        assert_eq!(w.remove_light(a), Some(l1));
+        assert_eq!(w.lights().map(|(id, _)| id).collect::<Vec<_>>(), vec![b]);
+    }
+
     #[test]
     fn shading_an_intersection() {
         let w = default_world();
@@ -166,6 +597,192 @@ mod tests {
         assert_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
     }
 
+    #[test]
+    fn shading_an_emissive_surface_adds_its_emission() {
+        let material = Material::new()
+            .with_diffuse(0.0)
+            .with_specular(0.0)
+            .with_ambient(0.0)
+            .with_emission(Color::new(0.5, 0.25, 1));
+        let w = World::new()
+            .with_lights(vec![PointLight::new(
+                Point::new(-10, 10, -10),
+                color::WHITE,
+            )])
+            .with_objects(vec![Sphere::new().with_material(material)]);
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+
+        assert_eq!(w.color_at(r), Color::new(0.5, 0.25, 1));
+    }
+
+    #[test]
+    fn ambient_occlusion_darkens_the_ambient_light_in_creases() {
+        // the point where the sphere touches the floor is in shadow, so only
+        // receives ambient light
+        let floor = Sphere::new().with_transform(Matrix::new().scale(100, 0.01, 100));
+        let ball = Sphere::new().with_transform(Matrix::new().translate(0, 1.01, 0));
+        let light = PointLight::new(Point::new(0, 10, 0), color::WHITE);
+        let r = Ray::new(Point::new(0, 0.5, -5), Vector::new(0, -0.5, 4.8).norm());
+        let w = World::new()
+            .with_lights(vec![light])
+            .with_objects(vec![floor.clone(), ball.clone()]);
+        let occluded = World::new()
+            .with_lights(vec![light])
+            .with_objects(vec![floor, ball])
+            .with_ambient_occlusion(AmbientOcclusion::new(64, 2.0));
+
+        let plain = w.color_at(r);
+        let darkened = occluded.color_at(r);
+
+        assert_eq!(plain, Color::new(0.1, 0.1, 0.1));
+        assert!(darkened.red() < 0.09 && darkened.red() > 0.0);
+    }
+
+    #[test]
+    fn ambient_occlusion_does_not_change_surfaces_in_the_open() {
+        let w = default_world();
+        let occluded = default_world().with_ambient_occlusion(AmbientOcclusion::new(16, 0.1));
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+
+        assert_eq!(occluded.color_at(r), w.color_at(r));
+    }
+
+    #[test]
+    fn a_ray_that_misses_sees_the_environment() {
+        let w = default_world().with_environment(Environment::Gradient {
+            bottom: color::BLACK,
+            top: Color::new(0.2, 0.4, 1),
+        });
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 1, 0));
+
+        assert_eq!(w.color_at(r), Color::new(0.2, 0.4, 1));
+    }
+
+    #[test]
+    fn fog_fades_surfaces_with_distance() {
+        let fog = Fog::new(Color::new(0.5, 0.5, 0.5), 0.2);
+        let w = default_world();
+        let fogged = default_world().with_fog(fog);
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+
+        assert_eq!(fogged.color_at(r), fog.apply(w.color_at(r), 4.0));
+    }
+
+    #[test]
+    fn fog_does_not_hide_the_environment() {
+        let w = default_world()
+            .with_fog(Fog::new(Color::new(0.5, 0.5, 0.5), 0.2))
+            .with_environment(Environment::Constant(Color::new(0, 0, 1)));
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 1, 0));
+
+        assert_eq!(w.color_at(r), Color::new(0, 0, 1));
+    }
+
+    fn volume(medium: Medium) -> Sphere {
+        Sphere::new()
+            .with_transform(Matrix::new().scale_u(3))
+            .with_material(Material::new().with_medium(medium))
+    }
+
+    #[test]
+    fn an_empty_volume_is_invisible() {
+        let w = default_world();
+        let mut with_volume = default_world();
+        with_volume.add_object(volume(Medium::default()));
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+
+        assert_eq!(with_volume.color_at(r), w.color_at(r));
+    }
+
+    #[test]
+    fn an_absorbing_volume_dims_what_is_behind_it() {
+        let medium = Medium::new(Color::new(0.5, 0.25, 0), color::BLACK);
+        let w = World::new()
+            .with_objects(vec![volume(medium)])
+            .with_environment(Environment::Constant(color::WHITE));
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+
+        // the ray passes through 6 units of the medium
+        assert_eq!(w.color_at(r), medium.transmittance(6.0));
+        assert_eq!(
+            w.color_at(r),
+            Color::new((-3.0_f64).exp(), (-1.5_f64).exp(), 1)
+        );
+    }
+
+    #[test]
+    fn a_ray_starting_inside_a_volume_is_dimmed_from_its_origin() {
+        let medium = Medium::new(Color::new(0.5, 0.5, 0.5), color::BLACK);
+        let w = World::new()
+            .with_objects(vec![volume(medium)])
+            .with_environment(Environment::Constant(color::WHITE));
+        let r = Ray::new(Point::new(0, 0, 0), Vector::new(0, 0, 1));
+
+        assert_eq!(w.color_at(r), medium.transmittance(3.0));
+    }
+
+    #[test]
+    fn a_scattering_volume_glows_in_the_light() {
+        let medium = Medium::new(color::BLACK, Color::new(0.1, 0.1, 0.1));
+        let light = PointLight::new(Point::new(0, 10, 0), color::WHITE);
+        let lit = World::new()
+            .with_lights(vec![light])
+            .with_objects(vec![volume(medium)]);
+        let shaded = World::new().with_lights(vec![light]).with_objects(vec![
+            volume(medium),
+            Sphere::new().with_transform(Matrix::new().scale(10, 0.1, 10).translate(0, 5, 0)),
+        ]);
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+
+        let glow = lit.color_at(r);
+
+        assert!(glow.red() > 0.05 && glow.red() < 1.0, "glow was {:?}", glow);
+        assert_eq!(shaded.color_at(r), color::BLACK);
+    }
+
+    #[test]
+    fn objects_inside_a_volume_are_seen_through_it() {
+        let medium = Medium::new(Color::new(0.1, 0.1, 0.1), color::BLACK);
+        let w = default_world();
+        let mut with_volume = default_world();
+        with_volume.add_object(volume(medium));
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+
+        // the ray travels 2 units through the medium to the outer sphere, and
+        // the light is dimmed on its way in, too
+        let seen = with_volume.color_at(r);
+
+        assert!(seen.red() < w.color_at(r).red() * medium.transmittance(2.0).red());
+        assert!(seen.red() > 0.0);
+    }
+
+    #[test]
+    fn volumes_dim_light_instead_of_casting_shadows() {
+        let medium = Medium::new(Color::new(0.5, 0.5, 0.5), color::BLACK);
+        let w = World::new().with_objects(vec![volume(medium)]);
+
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1)).with_interval(0.0, 10.0);
+        let t = w.light_transmittance(Point::new(0, 0, -5), Point::new(0, 0, 5), 0.0);
+
+        assert!(!w.is_occluded(r));
+        assert_eq!(t, medium.transmittance(6.0));
+    }
+
+    #[test]
+    fn the_emitters_are_the_objects_with_an_emission() {
+        let glowing = Material::new().with_emission(Color::new(1, 1, 1));
+        let w = World::new().with_objects(vec![
+            Sphere::new(),
+            Sphere::new().with_material(glowing),
+            Sphere::new(),
+        ]);
+
+        let emitters: Vec<&Shape> = w.emitters().collect();
+
+        assert_eq!(emitters.len(), 1);
+        assert_eq!(emitters[0].material(), glowing);
+    }
+
     #[test]
     fn shading_an_intersection_from_the_inside() {
         let mut w = default_world();
@@ -180,6 +797,18 @@ mod tests {
         assert_eq!(c, Color::new(0.90498, 0.90498, 0.90498));
     }
 
+    #[test]
+    fn intersect_a_world_with_a_bounded_ray() {
+        let w = default_world();
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1)).with_interval(4.25, 5.75);
+
+        let xs = w.intersect(r);
+
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t(), 4.5);
+        assert_eq!(xs[1].t(), 5.5);
+    }
+
     #[test]
     fn the_color_when_a_ray_misses() {
         let w = default_world();
@@ -218,35 +847,68 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn there_is_no_shadow_when_nothing_is_collinear_with_point_and_light() {
         let w = default_world();
         let p = Point::new(0, 10, 0);
 
-        assert_eq!(w.is_shadowed(p, w.lights[0]), false);
+        assert_eq!(w.is_shadowed(p, w.lights[0], 0.0), false);
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn the_shadow_when_an_object_is_between_the_point_and_the_light() {
         let w = default_world();
         let p = Point::new(10, -10, 10);
 
-        assert_eq!(w.is_shadowed(p, w.lights[0]), true);
+        assert_eq!(w.is_shadowed(p, w.lights[0], 0.0), true);
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn there_is_no_shadow_when_an_object_is_behind_the_light() {
         let w = default_world();
         let p = Point::new(-20, 20, -20);
 
-        assert_eq!(w.is_shadowed(p, w.lights[0]), false);
+        assert_eq!(w.is_shadowed(p, w.lights[0], 0.0), false);
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn there_is_no_shadow_when_an_object_is_behind_the_point() {
         let w = default_world();
         let p = Point::new(-2, 2, -2);
 
-        assert_eq!(w.is_shadowed(p, w.lights[0]), false);
+        assert_eq!(w.is_shadowed(p, w.lights[0], 0.0), false);
+    }
+
+    #[test]
+    fn a_ray_is_occluded_by_an_object_before_the_distance() {
+        let w = default_world();
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+
+        assert!(w.is_occluded(r.with_interval(0.0, 10.0)));
+        assert!(!w.is_occluded(r.with_interval(0.0, 4.0)));
+    }
+
+    #[test]
+    fn a_ray_is_not_occluded_by_objects_behind_it() {
+        let w = default_world();
+        let r = Ray::new(Point::new(0, 0, 5), Vector::new(0, 0, 1));
+
+        assert!(!w.is_occluded(r.with_interval(0.0, 100.0)));
+    }
+
+    #[test]
+    fn shadows_are_cast_by_moving_objects_at_the_ray_time() {
+        let blocker = Sphere::new().with_motion(Matrix::new(), Matrix::new().translate(0, 0, 50));
+        let w = World::new()
+            .with_lights(vec![PointLight::new(Point::new(0, 0, -10), color::WHITE)])
+            .with_objects(vec![blocker]);
+        let p = Point::new(0, 0, 10);
+
+        assert!(w.is_shadowed(p, w.lights[0], 0.0));
+        assert!(!w.is_shadowed(p, w.lights[0], 1.0));
     }
 
     #[test]
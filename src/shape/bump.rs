@@ -0,0 +1,364 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::geometry::{Point, Tuple, Vector};
+use crate::raytracer::Canvas;
+
+/// The distance used to estimate the gradient of a height function.
+const GRADIENT_STEP: f64 = 1e-4;
+
+/// A scalar height field over object space, used for bump mapping. Any
+/// `Fn(Point) -> f64` closure is a height function.
+pub trait BumpFunction: Send + Sync {
+    fn height(&self, point: Point) -> f64;
+}
+
+impl<F> BumpFunction for F
+where
+    F: Fn(Point) -> f64 + Send + Sync,
+{
+    fn height(&self, point: Point) -> f64 {
+        self(point)
+    }
+}
+
+/// A perturbation of a shape's shading normal, for surface detail without
+/// extra geometry. The shape's geometry, and so its silhouette and shadows,
+/// are unchanged.
+#[derive(Clone)]
+pub enum Bump {
+    /// A tangent-space normal map, wrapped around the shape using its UV
+    /// mapping. Colors encode normals as (x + 1) / 2, (y + 1) / 2 and
+    /// (z + 1) / 2, where x points along increasing u, y along increasing v,
+    /// and z out of the surface; this is the usual convention of modern
+    /// tools, so a flat map is (0.5, 0.5, 1). An empty map leaves the normal
+    /// as it is.
+    NormalMap(Arc<Canvas>),
+    /// Bumps given by a height function of the object-space point. The
+    /// normal is tilted against the height's slope along the surface, scaled
+    /// by `scale`.
+    Height {
+        function: Arc<dyn BumpFunction>,
+        scale: f64,
+    },
+}
+
+impl Bump {
+    /// Panics if the image is empty.
+    pub fn normal_map(image: Canvas) -> Bump {
+        assert!(
+            image.width() > 0 && image.height() > 0,
+            "a normal map needs at least one pixel"
+        );
+        Bump::NormalMap(Arc::new(image))
+    }
+
+    pub fn height(function: impl BumpFunction + 'static, scale: f64) -> Bump {
+        Bump::Height {
+            function: Arc::new(function),
+            scale,
+        }
+    }
+
+    /// Returns bumps from gradient noise with the given frequency, in
+    /// features per unit of object space.
+    pub fn noise(frequency: f64, scale: f64) -> Bump {
+        Bump::height(Noise::new(frequency), scale)
+    }
+
+    /// Perturbs the object-space `normal` at `point`. The shape supplies the
+    /// point's texture coordinates, and the tangents along which u and v
+    /// increase.
+    pub(crate) fn perturb(
+        &self,
+        point: Point,
+        normal: Vector,
+        uv: (f64, f64),
+        tangents: (Vector, Vector),
+    ) -> Vector {
+        let normal = normal.norm();
+
+        match self {
+            Bump::NormalMap(image) if image.width() == 0 || image.height() == 0 => normal,
+            Bump::NormalMap(image) => {
+                let (u, v) = uv;
+                let col = ((u * image.width() as f64).floor() as isize)
+                    .rem_euclid(image.width() as isize) as usize;
+                let row =
+                    (((1.0 - v) * image.height() as f64).floor() as usize).min(image.height() - 1);
+                let texel = image.get(col, row);
+
+                // make the tangents orthonormal, keeping their handedness
+                let (tangent, bitangent) = tangents;
+                let tangent = (tangent - normal * tangent.dot(normal)).norm();
+                let bitangent =
+                    (bitangent - normal * bitangent.dot(normal) - tangent * bitangent.dot(tangent))
+                        .norm();
+                let perturbed = tangent * (2.0 * texel.red() - 1.0)
+                    + bitangent * (2.0 * texel.green() - 1.0)
+                    + normal * (2.0 * texel.blue() - 1.0);
+                perturbed.norm()
+            }
+            Bump::Height { function, scale } => {
+                let gradient = gradient(function.as_ref(), point);
+                let along_surface = gradient - normal * gradient.dot(normal);
+                (normal - along_surface * *scale).norm()
+            }
+        }
+    }
+}
+
+fn gradient(function: &dyn BumpFunction, point: Point) -> Vector {
+    let h = GRADIENT_STEP;
+    let dx = Vector::new(h, 0, 0);
+    let dy = Vector::new(0, h, 0);
+    let dz = Vector::new(0, 0, h);
+
+    Vector::new(
+        function.height(point + dx) - function.height(point - dx),
+        function.height(point + dy) - function.height(point - dy),
+        function.height(point + dz) - function.height(point - dz),
+    ) / (2.0 * h)
+}
+
+impl fmt::Debug for Bump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bump::NormalMap(image) => f
+                .debug_tuple("NormalMap")
+                .field(&(image.width(), image.height()))
+                .finish(),
+            Bump::Height { scale, .. } => f.debug_struct("Height").field("scale", scale).finish(),
+        }
+    }
+}
+
+// Bumps are equal if they share the same image or function.
+impl PartialEq for Bump {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Bump::NormalMap(a), Bump::NormalMap(b)) => Arc::ptr_eq(a, b),
+            (
+                Bump::Height {
+                    function: a,
+                    scale: sa,
+                },
+                Bump::Height {
+                    function: b,
+                    scale: sb,
+                },
+            ) => Arc::ptr_eq(a, b) && sa == sb,
+            _ => false,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Perlin gradient noise, with values roughly in [-1, 1], and zero at every
+/// point of the integer lattice (before scaling by the frequency).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Noise {
+    frequency: f64,
+}
+
+impl Noise {
+    pub fn new(frequency: f64) -> Noise {
+        Noise { frequency }
+    }
+
+    pub fn at(&self, point: Point) -> f64 {
+        let x = point.x() * self.frequency;
+        let y = point.y() * self.frequency;
+        let z = point.z() * self.frequency;
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+        let (ix, iy, iz) = (x0 as i64, y0 as i64, z0 as i64);
+
+        let corner = |dx: i64, dy: i64, dz: i64| {
+            let g = lattice_gradient(ix + dx, iy + dy, iz + dz);
+            g.0 * (fx - dx as f64) + g.1 * (fy - dy as f64) + g.2 * (fz - dz as f64)
+        };
+
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+}
+
+impl BumpFunction for Noise {
+    fn height(&self, point: Point) -> f64 {
+        self.at(point)
+    }
+}
+
+// Perlin's twelve gradients, towards the edges of a cube.
+const GRADIENTS: [(f64, f64, f64); 12] = [
+    (1.0, 1.0, 0.0),
+    (-1.0, 1.0, 0.0),
+    (1.0, -1.0, 0.0),
+    (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0),
+    (-1.0, 0.0, 1.0),
+    (1.0, 0.0, -1.0),
+    (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0),
+    (0.0, -1.0, 1.0),
+    (0.0, 1.0, -1.0),
+    (0.0, -1.0, -1.0),
+];
+
+// Picks a gradient for a lattice point by hashing its coordinates, instead of
+// using a permutation table, so the noise doesn't repeat.
+fn lattice_gradient(x: i64, y: i64, z: i64) -> (f64, f64, f64) {
+    let mut h = (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+        ^ (z as u64).wrapping_mul(0x1656_67b1_9e37_79f9);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    GRADIENTS[(h % 12) as usize]
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracer::Color;
+
+    // The tangents along u and v for a surface facing +y.
+    fn tangents() -> (Vector, Vector) {
+        (Vector::new(1, 0, 0), Vector::new(0, 0, -1))
+    }
+
+    #[test]
+    fn noise_is_zero_on_the_lattice() {
+        let noise = Noise::new(1.0);
+
+        assert_eq!(noise.at(Point::new(0, 0, 0)), 0.0);
+        assert_eq!(noise.at(Point::new(3, -2, 7)), 0.0);
+        assert_eq!(Noise::new(2.0).at(Point::new(0.5, 1.5, -0.5)), 0.0);
+    }
+
+    #[test]
+    fn noise_is_smooth_and_bounded() {
+        let noise = Noise::new(1.0);
+        let mut p = Point::new(0.1, 0.2, 0.3);
+        let step = Vector::new(0.013, 0.007, 0.011);
+        let mut previous = noise.at(p);
+        let mut varies = false;
+
+        for _ in 0..1000 {
+            p = p + step;
+            let n = noise.at(p);
+            assert!(n.abs() <= 1.5);
+            assert!((n - previous).abs() < 0.1);
+            varies |= n != previous;
+            previous = n;
+        }
+
+        assert!(varies);
+    }
+
+    #[test]
+    fn a_flat_normal_map_leaves_the_normal_unchanged() {
+        let mut image = Canvas::new(4, 2);
+        for col in 0..4 {
+            for row in 0..2 {
+                image.set(col, row, Color::new(0.5, 0.5, 1));
+            }
+        }
+        let bump = Bump::normal_map(image);
+        let n = Vector::new(0, 1, 0);
+
+        let perturbed = bump.perturb(Point::new(0, 1, 0), n, (0.3, 0.6), tangents());
+
+        assert_eq!(perturbed, n);
+    }
+
+    #[test]
+    #[should_panic(expected = "a normal map needs at least one pixel")]
+    fn a_normal_map_cannot_be_empty() {
+        Bump::normal_map(Canvas::new(0, 0));
+    }
+
+    #[test]
+    fn an_empty_normal_map_leaves_the_normal_unchanged() {
+        let bump = Bump::NormalMap(Arc::new(Canvas::new(0, 0)));
+        let n = Vector::new(0, 1, 0);
+
+        let perturbed = bump.perturb(Point::new(0, 1, 0), n, (0.5, 0.5), tangents());
+
+        assert_eq!(perturbed, n);
+    }
+
+    #[test]
+    fn a_normal_map_tilts_the_normal_in_tangent_space() {
+        let mut image = Canvas::new(2, 2);
+        image.set(0, 0, Color::new(0.5, 0.5, 1));
+        image.set(1, 0, Color::new(1, 0.5, 0.5));
+        image.set(0, 1, Color::new(0.5, 1, 0.5));
+        image.set(1, 1, Color::new(0.5, 0.5, 1));
+        let bump = Bump::normal_map(image);
+        let n = Vector::new(0, 0, 1);
+        let tangents = (Vector::new(0, 1, 0), Vector::new(-1, 0, 0));
+
+        // the top right texel points along u
+        let along_u = bump.perturb(Point::new(0, 0, 1), n, (0.75, 0.75), tangents);
+        // the bottom left points along v
+        let along_v = bump.perturb(Point::new(0, 0, 1), n, (0.25, 0.25), tangents);
+
+        assert_eq!(along_u, Vector::new(0, 1, 0));
+        assert_eq!(along_v, Vector::new(-1, 0, 0));
+    }
+
+    #[test]
+    fn a_constant_height_leaves_the_normal_unchanged() {
+        let bump = Bump::height(|_: Point| 2.0, 1.0);
+        let n = Vector::new(0, 1, 0);
+
+        let perturbed = bump.perturb(Point::new(0, 1, 0), n, (0.0, 0.0), tangents());
+
+        assert_eq!(perturbed, n);
+    }
+
+    #[test]
+    fn a_slope_tilts_the_normal_downhill() {
+        // the height rises along +x, so the normal leans towards -x
+        let bump = Bump::height(|p: Point| p.x(), 1.0);
+        let n = Vector::new(0, 1, 0);
+
+        let perturbed = bump.perturb(Point::new(0, 1, 0), n, (0.0, 0.0), tangents());
+
+        assert_eq!(perturbed, Vector::new(-1, 1, 0).norm());
+    }
+
+    #[test]
+    fn bumps_are_equal_when_they_share_a_function() {
+        let a = Bump::noise(4.0, 0.5);
+        let b = a.clone();
+
+        assert_eq!(a, b);
+        assert_ne!(a, Bump::noise(4.0, 0.5));
+    }
+}
@@ -1,5 +1,8 @@
-use crate::geometry::{point, Matrix, Point, Vector};
+use std::f64::consts::PI;
+
+use crate::geometry::{point, Matrix, Motion, Point, Tuple, Vector};
 use crate::raytracer::{Intersection, Intersections, Material, Ray};
+use crate::shape::Bump;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Sphere {
@@ -8,20 +11,44 @@ pub struct Sphere {
     transform: Matrix,
     inverse_transform: Matrix,
     transposed_inverse_transform: Matrix,
+    motion: Option<Motion>,
+    bump: Option<Bump>,
 }
 
-impl<'a> Sphere {
+impl Sphere {
     pub fn new() -> Sphere {
         Sphere {
             material: Material::new(),
             transform: Matrix::new(),
             inverse_transform: Matrix::new(),
             transposed_inverse_transform: Matrix::new(),
+            motion: None,
+            bump: None,
+        }
+    }
+
+    pub fn intersect(&self, ray: Ray) -> Intersections<'_> {
+        match self.intersection_ts(ray) {
+            Some((t1, t2)) => [t1, t2]
+                .into_iter()
+                .filter(|&t| ray.contains(t))
+                .map(|t| Intersection::new(t, self))
+                .collect(),
+            None => Intersections::new(),
         }
     }
 
-    pub fn intersect(&'a self, ray: Ray) -> Intersections<'a> {
-        let t_ray = ray.transform(&self.inverse_transform);
+    /// Returns true if the ray intersects the sphere anywhere within the
+    /// ray's interval, without building an `Intersections` list.
+    pub fn intersects(&self, ray: Ray) -> bool {
+        match self.intersection_ts(ray) {
+            Some((t1, t2)) => ray.contains(t1) || ray.contains(t2),
+            None => false,
+        }
+    }
+
+    fn intersection_ts(&self, ray: Ray) -> Option<(f64, f64)> {
+        let t_ray = ray.transform(&self.inverse_transform_at(ray.time())?);
 
         // Vector from the sphere's center, to the ray's origin
         let to_ray = t_ray.origin() - Point::new(0, 0, 0);
@@ -35,23 +62,85 @@ impl<'a> Sphere {
         if discriminant >= 0.0 {
             let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
             let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
-
-            let i1 = Intersection::new(t1, self);
-            let i2 = Intersection::new(t2, self);
-
-            Intersections::from(vec![i1, i2])
+            Some((t1, t2))
         } else {
-            Intersections::new()
+            None
         }
     }
 
     pub fn normal_at(&self, world_point: Point) -> Vector {
-        let object_point = self.inverse_transform * world_point;
+        self.normal_at_time(world_point, 0.0)
+    }
+
+    /// Returns the normal at the given point, with the sphere positioned
+    /// where it was at the given time.
+    pub fn normal_at_time(&self, world_point: Point, time: f64) -> Vector {
+        let (inverse_transform, transposed_inverse_transform) = match self.motion {
+            Some(_) => {
+                // a sphere scaled down to nothing has no surface, so any
+                // normal will do
+                let inverse_transform = self.inverse_transform_at(time).unwrap_or_default();
+                (inverse_transform, inverse_transform.transpose())
+            }
+            None => (self.inverse_transform, self.transposed_inverse_transform),
+        };
+        let object_point = inverse_transform * world_point;
         let object_normal = object_point - point::ORIGIN;
-        let world_normal = self.transposed_inverse_transform * object_normal;
+        let object_normal = match &self.bump {
+            Some(bump) => bump.perturb(
+                object_point,
+                object_normal,
+                uv_at(object_point),
+                tangents_at(object_point),
+            ),
+            None => object_normal,
+        };
+        let world_normal = transposed_inverse_transform * object_normal;
         world_normal.norm()
     }
 
+    /// Maps two uniform random numbers in [0, 1) to a point on the surface of
+    /// the sphere at the given time. Returns the point, the surface normal
+    /// there, and the probability density of choosing that point, per unit
+    /// of surface area. Used to sample emissive spheres as area lights.
+    pub fn sample_surface(&self, u: f64, v: f64, time: f64) -> (Point, Vector, f64) {
+        let z = 1.0 - 2.0 * u;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        let object_normal = Vector::new(r * phi.cos(), r * phi.sin(), z);
+
+        let (transform, transposed_inverse_transform) = match self.motion {
+            Some(motion) => {
+                let transform = motion.at(time);
+                let inverse_transform = self.inverse_transform_at(time).unwrap_or_default();
+                (transform, inverse_transform.transpose())
+            }
+            None => (self.transform, self.transposed_inverse_transform),
+        };
+        let world_point = transform * (point::ORIGIN + object_normal);
+        let world_normal = transposed_inverse_transform * object_normal;
+
+        // The transform stretches a small patch of the unit sphere by the
+        // determinant, divided by how much it stretches the patch's normal.
+        let area_scale = transform.determinant().abs() * world_normal.mag();
+        let pdf = 1.0 / (4.0 * PI * area_scale);
+
+        (world_point, world_normal.norm(), pdf)
+    }
+
+    // A stationary sphere uses its cached inverse; a moving one has to
+    // interpolate and invert its transform for every ray. Returns None when
+    // a moving sphere is scaled down to nothing, and so can't be hit.
+    fn inverse_transform_at(&self, time: f64) -> Option<Matrix> {
+        match self.motion {
+            Some(motion) => {
+                let transform = motion.at(time);
+                transform.is_invertible().then(|| transform.inverse())
+            }
+            None => Some(self.inverse_transform),
+        }
+    }
+
     pub fn transform(&self) -> &Matrix {
         &self.transform
     }
@@ -60,9 +149,39 @@ impl<'a> Sphere {
         self.transform = transform;
         self.inverse_transform = transform.inverse();
         self.transposed_inverse_transform = self.inverse_transform.transpose();
+        self.motion = None;
+        self
+    }
+
+    /// Makes the sphere move from `start` at time 0 to `end` at time 1.
+    /// Rays sample the sphere at their own time; see `Camera::with_shutter`.
+    /// Either transform may scale the sphere down to nothing.
+    pub fn with_motion(mut self, start: Matrix, end: Matrix) -> Self {
+        // a moving sphere inverts its transform at each time instead of
+        // caching the inverse, which `start` may not have
+        self.transform = start;
+        self.inverse_transform = Matrix::new();
+        self.transposed_inverse_transform = Matrix::new();
+        self.motion = Some(Motion::new(start, end));
+        self
+    }
+
+    pub fn motion(&self) -> Option<Motion> {
+        self.motion
+    }
+
+    /// Perturbs the sphere's shading normal with a normal map or bump
+    /// function. Normal maps are wrapped around the sphere with u running
+    /// around the y axis, and v from the bottom pole to the top.
+    pub fn with_bump(mut self, bump: Bump) -> Self {
+        self.bump = Some(bump);
         self
     }
 
+    pub fn bump(&self) -> Option<&Bump> {
+        self.bump.as_ref()
+    }
+
     pub fn material(&self) -> Material {
         self.material
     }
@@ -73,6 +192,32 @@ impl<'a> Sphere {
     }
 }
 
+// The spherical texture coordinates of a point on the unit sphere.
+fn uv_at(p: Point) -> (f64, f64) {
+    let theta = p.x().atan2(p.z());
+    let radius = (p - point::ORIGIN).mag();
+    let phi = (p.y() / radius).clamp(-1.0, 1.0).acos();
+
+    let u = 1.0 - (theta / (2.0 * PI) + 0.5);
+    let v = 1.0 - phi / PI;
+    (u, v)
+}
+
+// The directions in which u and v increase at a point on the unit sphere.
+// Both vanish at the poles, where any tangents will do.
+fn tangents_at(p: Point) -> (Vector, Vector) {
+    let tangent = Vector::new(-p.z(), 0, p.x());
+    if tangent.mag() < 1e-9 {
+        return (Vector::new(1, 0, 0), Vector::new(0, 0, -p.y().signum()));
+    }
+    let bitangent = Vector::new(
+        -p.x() * p.y(),
+        p.x() * p.x() + p.z() * p.z(),
+        -p.y() * p.z(),
+    );
+    (tangent, bitangent)
+}
+
 impl Default for Sphere {
     fn default() -> Self {
         Self::new()
@@ -81,12 +226,12 @@ impl Default for Sphere {
 
 #[cfg(test)]
 mod tests {
-    use std::f64::consts::{PI, SQRT_2};
+    use std::f64::consts::{FRAC_1_SQRT_2, SQRT_2};
 
     use super::*;
     use crate::{
         geometry::{Matrix, Vector},
-        raytracer::Material,
+        raytracer::{Canvas, Color, Material},
     };
 
     #[test]
@@ -135,6 +280,39 @@ mod tests {
         assert_eq!(xs[1].t(), -4.0);
     }
 
+    #[test]
+    fn intersect_only_reports_intersections_in_the_ray_interval() {
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1)).with_interval(5.0, 10.0);
+        let s = Sphere::new();
+
+        let xs = s.intersect(r);
+
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t(), 6.0);
+    }
+
+    #[test]
+    fn intersecting_a_scaled_sphere_honors_the_ray_interval() {
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1)).with_interval(0.0, 5.0);
+        let s = Sphere::new().with_transform(Matrix::new().scale(2, 2, 2));
+
+        let xs = s.intersect(r);
+
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t(), 3.0);
+    }
+
+    #[test]
+    fn intersects_only_counts_intersections_in_the_ray_interval() {
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+        let s = Sphere::new();
+
+        assert!(s.intersects(r));
+        assert!(s.intersects(r.with_interval(0.0, 4.5)));
+        assert!(!s.intersects(r.with_interval(0.0, 4.0)));
+        assert!(!s.intersects(r.with_interval(6.5, 10.0)));
+    }
+
     #[test]
     fn intersect_sets_the_object_on_the_intersection() {
         let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
@@ -232,6 +410,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn computing_the_normal_on_a_translated_sphere() {
         let s = Sphere::new().with_transform(Matrix::new().translate(0, 1, 0));
 
@@ -250,6 +429,78 @@ mod tests {
         assert_eq!(n, Vector::new(0, 0.97014, -0.24254));
     }
 
+    #[test]
+    fn a_sphere_is_stationary_by_default() {
+        let s = Sphere::new();
+
+        assert_eq!(s.motion(), None);
+    }
+
+    #[test]
+    fn intersecting_a_moving_sphere_at_different_times() {
+        let s = Sphere::new().with_motion(Matrix::new(), Matrix::new().translate(0, 0, 2));
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+
+        let xs = s.intersect(r);
+        assert_eq!(xs[0].t(), 4.0);
+
+        let xs = s.intersect(r.with_time(0.5));
+        assert_eq!(xs[0].t(), 5.0);
+
+        let xs = s.intersect(r.with_time(1.0));
+        assert_eq!(xs[0].t(), 6.0);
+    }
+
+    #[test]
+    fn a_moving_sphere_can_be_missed_at_some_times() {
+        let s = Sphere::new().with_motion(Matrix::new(), Matrix::new().translate(5, 0, 0));
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+
+        assert!(s.intersects(r.with_time(0.0)));
+        assert!(!s.intersects(r.with_time(1.0)));
+    }
+
+    #[test]
+    fn the_normal_on_a_moving_sphere_depends_on_time() {
+        let s = Sphere::new().with_motion(Matrix::new(), Matrix::new().translate(0, 2, 0));
+
+        assert_eq!(
+            s.normal_at_time(Point::new(0, 1, 0), 0.0),
+            Vector::new(0, 1, 0)
+        );
+        assert_eq!(
+            s.normal_at_time(Point::new(0, 1, 0), 1.0),
+            Vector::new(0, -1, 0)
+        );
+    }
+
+    #[test]
+    fn a_moving_sphere_can_grow_from_nothing() {
+        let s = Sphere::new().with_motion(Matrix::new().scale_u(0), Matrix::new());
+        let r = Ray::new(Point::new(0, 0, -5), Vector::new(0, 0, 1));
+
+        assert!(s.intersect(r).is_empty());
+        assert!(!s.intersects(r));
+
+        let xs = s.intersect(r.with_time(0.5));
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t(), 4.5);
+        assert!(s.intersects(r.with_time(0.5)));
+
+        // there's no surface to sample at the start
+        let (_, _, pdf) = s.sample_surface(0.3, 0.6, 0.0);
+        assert!(pdf.is_infinite());
+    }
+
+    #[test]
+    fn setting_a_transform_stops_a_sphere_moving() {
+        let s = Sphere::new()
+            .with_motion(Matrix::new(), Matrix::new().translate(0, 2, 0))
+            .with_transform(Matrix::new().translate(1, 0, 0));
+
+        assert_eq!(s.motion(), None);
+    }
+
     #[test]
     fn a_sphere_has_a_default_material() {
         let s = Sphere::new();
@@ -266,4 +517,75 @@ mod tests {
 
         assert_eq!(s.material(), m);
     }
+
+    #[test]
+    fn sampled_points_lie_on_the_surface() {
+        let s = Sphere::new().with_transform(Matrix::new().scale(1, 2, 3).translate(1, 0, -1));
+
+        for (u, v) in [(0.0, 0.0), (0.25, 0.5), (0.5, 0.1), (0.9, 0.75)] {
+            let (p, n, _) = s.sample_surface(u, v, 0.0);
+            let object_point = s.inverse_transform * p;
+
+            assert!(((object_point - point::ORIGIN).mag() - 1.0).abs() < 1e-9);
+            assert_eq!(n, s.normal_at(p));
+        }
+    }
+
+    #[test]
+    fn the_sampling_density_of_a_scaled_sphere_is_one_over_its_area() {
+        let s = Sphere::new().with_transform(Matrix::new().scale_u(2).translate(0, 5, 0));
+
+        let (_, _, pdf) = s.sample_surface(0.3, 0.6, 0.0);
+
+        assert!((pdf - 1.0 / (16.0 * PI)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sampling_a_moving_sphere_uses_its_position_at_the_given_time() {
+        let s = Sphere::new().with_motion(Matrix::new(), Matrix::new().translate(4, 0, 0));
+
+        let (p, n, _) = s.sample_surface(0.0, 0.0, 0.5);
+
+        assert_eq!(p, Point::new(2, 0, 1));
+        assert_eq!(n, Vector::new(0, 0, 1));
+    }
+
+    #[test]
+    fn a_bump_perturbs_the_normal() {
+        let s = Sphere::new()
+            .with_transform(Matrix::new().translate(0, 1, 0))
+            .with_bump(Bump::height(|p: Point| p.x(), 1.0));
+
+        let n = s.normal_at(Point::new(0, 2, 0));
+
+        assert_eq!(n, Vector::new(-1, 1, 0).norm());
+    }
+
+    #[test]
+    fn a_normal_map_is_wrapped_around_the_sphere() {
+        // a map that tilts every normal up, towards increasing v
+        let mut image = Canvas::new(8, 4);
+        for col in 0..8 {
+            for row in 0..4 {
+                image.set(col, row, Color::new(0.5, 1, 0.5));
+            }
+        }
+        let s = Sphere::new().with_bump(Bump::normal_map(image));
+
+        assert_eq!(s.normal_at(Point::new(0, 0, -1)), Vector::new(0, 1, 0));
+        assert_eq!(s.normal_at(Point::new(1, 0, 0)), Vector::new(0, 1, 0));
+        assert_eq!(
+            s.normal_at(Point::new(0, FRAC_1_SQRT_2, FRAC_1_SQRT_2)),
+            Vector::new(0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2)
+        );
+    }
+
+    #[test]
+    fn the_texture_coordinates_of_points_on_a_sphere() {
+        assert_eq!(uv_at(Point::new(0, 0, -1)), (0.0, 0.5));
+        assert_eq!(uv_at(Point::new(1, 0, 0)), (0.25, 0.5));
+        assert_eq!(uv_at(Point::new(0, 0, 1)), (0.5, 0.5));
+        assert_eq!(uv_at(Point::new(0, 1, 0)), (0.5, 1.0));
+        assert_eq!(uv_at(Point::new(0, -1, 0)), (0.5, 0.0));
+    }
 }